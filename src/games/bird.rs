@@ -1,88 +1,241 @@
 use crate::gui::*;
-
-const DOWN: i32 = 1;
-const UP: i32 = -8;
-const BIRD: i32 = 60;
-const PIPE_WIDTH: i32 = BIRD * 2;
-const PIPE_GAP: i32 = BIRD * 5;
-const PIPE_SPEED: i32 = BIRD / 15;
+use crate::models::bird::*;
+use std::collections::HashMap;
 
 #[derive(Default)]
-struct Bird {
-    position: (i32, i32),
-    velocity: i32,
+pub struct Model {
+    path: String,
+    scene: Scene,
+    difficulty: Difficulty,
+    control: Control,
+    field: (f32, f32),
+    bird: Bird,
+    world: World,
+    // best score and its ghost per difficulty label
+    best: HashMap<String, i32>,
+    ghosts: HashMap<String, Ghost>,
+    run: Ghost,
+    // race the ghost over its course, otherwise every run gets a new course
+    race: bool,
+    brain: Brain,
+    flock: Flock,
 }
 
-impl Bird {
-    fn new(height: i32) -> Self {
-        Self {
-            position: (100, height / 2),
-            velocity: 0,
+impl Model {
+    fn brain_path(&self) -> String {
+        format!("{}.brain", self.path)
+    }
+    fn reset(&mut self) {
+        self.bird = Bird::new(self.field.1);
+        match self.control {
+            Control::Player => {
+                // race the ghost over its own course when asked and there is one
+                let seed = match self.ghost() {
+                    Some(ghost) if self.race => ghost.seed,
+                    _ => rand::rng().random(),
+                };
+                self.world = World::new(seed, self.field);
+                self.run = Ghost::new(seed, &self.difficulty, self.field.1);
+                self.scene = Scene::Ready;
+            }
+            Control::Trained => {
+                self.world = World::new(rand::rng().random(), self.field);
+                self.scene = Scene::Playing;
+            }
+            Control::Evolve => {
+                self.world = self.flock.launch(self.field);
+                self.scene = Scene::Playing;
+            }
         }
     }
-}
-
-struct Pipe {
-    x: i32,
-    gap_center_y: i32,
-    scored: bool,
-}
-
-impl Pipe {
-    fn top_rect(&self) -> (i32, i32, i32, i32) {
-        let top_height = self.gap_center_y - (PIPE_GAP / 2);
-        (self.x, 0, PIPE_WIDTH, top_height)
+    // the ghost of this difficulty when it was flown at this window height
+    fn ghost(&self) -> Option<&Ghost> {
+        self.ghosts
+            .get(self.difficulty.label())
+            .filter(|ghost| ghost.fits(&self.difficulty, self.field.1))
     }
-
-    fn bottom_rect(&self, height: i32) -> (i32, i32, i32, i32) {
-        let bottom_y = self.gap_center_y + (PIPE_GAP / 2);
-        let bottom_height = height - bottom_y;
-        (self.x, bottom_y, PIPE_WIDTH, bottom_height)
+    fn best(&self) -> i32 {
+        self.best
+            .get(self.difficulty.label())
+            .copied()
+            .unwrap_or_default()
     }
-}
+    fn crash(&mut self) {
+        if let Control::Player = self.control
+            && self.world.score > self.best()
+        {
+            let label = self.difficulty.label().to_string();
+            self.best.insert(label.clone(), self.world.score);
+            self.ghosts.insert(label, std::mem::take(&mut self.run));
+        }
+        self.scene = Scene::GameOver;
+    }
+    // the whole generation dead or through: breed the next one on a new course
+    fn next_generation(&mut self) {
+        if self.flock.evolve(self.world.score) {
+            self.brain = self.flock.champion.clone();
+            self.brain.save(&self.brain_path(), self.flock.record);
+        }
+        self.world = self.flock.launch(self.field);
+    }
+    fn draw_game(&self) {
+        for pipe in &self.world.pipes {
+            for (x, y, w, h) in [pipe.top_rect(), pipe.bottom_rect(self.field.1)] {
+                draw::draw_rect_fill(x as i32, y as i32, w as i32, h as i32, Color::Green);
+            }
+        }
 
-#[derive(Default)]
-pub struct Model {
-    field: (i32, i32),
-    bird: Bird,
-    pipes: Vec<Pipe>,
-    score: i32,
-    play: bool,
-    timer: f32,
-}
+        if let Control::Evolve = self.control {
+            let shade = Color::color_average(Color::Selection, Color::Background, 0.35);
+            for pilot in self.flock.pilots.iter().filter(|pilot| pilot.alive) {
+                let (x, y) = pilot.bird.position;
+                draw::draw_circle_fill(x as i32, y as i32, BIRD as i32, shade);
+            }
+            draw::set_draw_color(Color::Foreground);
+            draw::draw_text2(
+                &format!(
+                    "Generation {}   Alive {}   Score {}   Best {}",
+                    self.flock.generation,
+                    self.flock.alive(),
+                    self.world.score,
+                    self.flock.record.max(0)
+                ),
+                0,
+                0,
+                self.field.0 as i32,
+                BIRD as i32,
+                Align::Center,
+            );
+            return;
+        }
 
-impl Model {
-    fn reset(&mut self) {
-        self.bird = Bird::new(self.field.1);
-        self.pipes = Vec::new();
-        self.score = 0;
+        if let Control::Player = self.control
+            && self.race
+            && let Some(ghost) = self.ghost()
+            && ghost.seed == self.world.course.seed
+            && let Some(y) = ghost.at(self.world.distance)
+        {
+            draw::draw_circle_fill(
+                self.bird.position.0 as i32,
+                y as i32,
+                BIRD as i32,
+                Color::color_average(Color::Selection, Color::Background, 0.35),
+            );
+        }
+
+        let (x, y) = (self.bird.position.0 as i32, self.bird.position.1 as i32);
+        draw::draw_circle_fill(x, y, BIRD as i32, Color::Selection);
+
+        draw::set_draw_color(Color::Background2);
+        draw::draw_text2(
+            &self.world.score.to_string(),
+            x,
+            y,
+            BIRD as i32,
+            BIRD as i32,
+            Align::Center,
+        );
     }
 }
 
 impl Console for Model {
-    fn load(&mut self, _path: &str) {}
-    fn exit(&self, _path: &str) {}
+    fn load(&mut self, path: &str) {
+        self.path = path.to_string();
+        for (key, value) in load_lines(path) {
+            match (key.as_str(), value.as_str()) {
+                ("difficulty", value) => {
+                    self.difficulty = Difficulty::from_label(value).unwrap_or_default()
+                }
+                ("best", value) => {
+                    if let Some((label, score)) = value.split_once(' ')
+                        && let Some(difficulty) = Difficulty::from_label(label)
+                    {
+                        self.best.insert(
+                            difficulty.label().to_string(),
+                            score.parse().unwrap_or_default(),
+                        );
+                    }
+                }
+                ("ghost", value) => {
+                    if let Some(ghost) = Ghost::from_line(value) {
+                        self.ghosts.insert(ghost.difficulty.clone(), ghost);
+                    }
+                }
+                _ => {}
+            }
+        }
+        if let Some((brain, score)) = Brain::load(&self.brain_path()) {
+            self.brain = brain;
+            self.flock.record = score;
+        }
+    }
+    fn exit(&self, path: &str) {
+        let mut save = vec![("difficulty", self.difficulty.label().to_string())];
+        for (label, score) in &self.best {
+            save.push(("best", format!("{label} {score}")));
+        }
+        for ghost in self.ghosts.values().filter(|ghost| !ghost.path.is_empty()) {
+            save.push(("ghost", ghost.to_line()));
+        }
+        save_lines(path, &save);
+    }
     fn handle(&mut self, window: &mut Window, event: Event) -> bool {
         match event {
             Event::Focus => true,
             Event::Resize => {
-                self.play = false;
-                self.field = (window.w(), window.h());
+                self.scene = Scene::Welcome;
+                self.field = (window.w() as f32, window.h() as f32);
                 true
             }
             Event::KeyDown => {
+                const PILOT: Key = Key::from_char('m');
+                const RACE: Key = Key::from_char('g');
                 match event_key() {
-                    Key::Escape => match self.play {
-                        true => self.play = false,
-                        false => crate::Model::connect(window),
+                    Key::Escape => match self.scene {
+                        Scene::Welcome => {
+                            self.exit(&self.path);
+                            crate::Model::connect(window);
+                        }
+                        Scene::Playing => self.scene = Scene::Paused,
+                        _ => self.scene = Scene::Welcome,
+                    },
+                    Key::Enter => match self.scene {
+                        Scene::Playing => self.scene = Scene::Paused,
+                        Scene::Paused => self.scene = Scene::Playing,
+                        Scene::Welcome | Scene::GameOver => {
+                            // nothing to fly the trained pilot with yet
+                            if !matches!(self.control, Control::Trained) || self.brain.ready() {
+                                self.reset();
+                            }
+                        }
+                        Scene::Ready => {}
+                    },
+                    Key::Up => match (&self.scene, &self.control) {
+                        (Scene::Ready, Control::Player) => {
+                            self.bird.flap();
+                            self.scene = Scene::Playing;
+                        }
+                        (Scene::Playing, Control::Player) => self.bird.flap(),
+                        _ => {}
                     },
-                    Key::Enter => {
-                        if !self.play {
-                            self.reset();
-                            self.play = true;
+                    Key::Tab => {
+                        if let Scene::Welcome = self.scene {
+                            self.difficulty = self.difficulty.switch();
+                            window.redraw();
+                        }
+                    }
+                    PILOT => {
+                        if let Scene::Welcome = self.scene {
+                            self.control = self.control.switch();
+                            window.redraw();
+                        }
+                    }
+                    RACE => {
+                        if let Scene::Welcome = self.scene {
+                            self.race = !self.race;
+                            window.redraw();
                         }
                     }
-                    Key::Up => self.bird.velocity = UP,
                     _ => return false,
                 };
                 true
@@ -91,116 +244,84 @@ impl Console for Model {
         }
     }
     fn update(&mut self, dt: f32) {
-        if !self.play {
+        let Scene::Playing = self.scene else {
             return;
-        }
-        self.bird.velocity += DOWN;
-        self.bird.position.1 += self.bird.velocity;
-
-        for pipe in &mut self.pipes {
-            pipe.x -= PIPE_SPEED;
-        }
-
-        self.timer += dt;
-        if self.timer > 1.5 {
-            self.pipes.push(Pipe {
-                x: self.field.0,
-                gap_center_y: rand::rng()
-                    .random_range((PIPE_GAP / 2)..(self.field.1 - (PIPE_GAP / 2))),
-                scored: false,
-            });
-            self.timer = 0.0;
-        }
-
-        self.pipes.retain(|pipe| pipe.x > -PIPE_WIDTH);
-
-        for pipe in &mut self.pipes {
-            if !pipe.scored && self.bird.position.0 > pipe.x + PIPE_WIDTH {
-                self.score += 1;
-                pipe.scored = true;
+        };
+        let dt = dt.min(MAX_STEP);
+        if let Control::Evolve = self.control {
+            self.world.update(&self.difficulty, dt);
+            self.flock.update(&self.world, dt);
+            if self.flock.alive() == 0 || self.world.score >= TRAIN_SCORE {
+                self.next_generation();
             }
+            return;
         }
 
-        if self.bird.position.1 - BIRD < 0 || self.bird.position.1 + BIRD > self.field.1 {
-            self.play = false;
+        if let Control::Trained = self.control
+            && self.brain.think(&self.bird, &self.world)
+        {
+            self.bird.flap();
         }
+        self.bird.update(dt);
+        self.world.update(&self.difficulty, dt);
+        self.run.record(self.world.distance, self.bird.position.1);
 
-        for pipe in &self.pipes {
-            let bird_collides =
-                check_collision_circle_rec_manual(self.bird.position, BIRD, pipe.top_rect())
-                    || check_collision_circle_rec_manual(
-                        self.bird.position,
-                        BIRD,
-                        pipe.bottom_rect(self.field.1),
-                    );
-
-            if bird_collides {
-                self.play = false;
-                break;
-            }
+        if self.world.hits(&self.bird) {
+            self.crash();
         }
     }
     fn draw(&self, window: &mut Window) {
-        if self.play {
-            draw::draw_rect_fill(0, 0, window.width(), window.height(), Color::Background);
-            for pipe in &self.pipes {
-                let top_rect = pipe.top_rect();
-                let bottom_rect = pipe.bottom_rect(self.field.1);
-                draw::draw_rect_fill(top_rect.0, top_rect.1, top_rect.2, top_rect.3, Color::Green);
-                draw::draw_rect_fill(
-                    bottom_rect.0,
-                    bottom_rect.1,
-                    bottom_rect.2,
-                    bottom_rect.3,
-                    Color::Green,
-                );
-            }
-
-            draw::draw_circle_fill(
-                self.bird.position.0,
-                self.bird.position.1,
-                BIRD,
-                Color::Selection,
-            );
-
-            draw::set_draw_color(Color::Background2);
-            draw::draw_text2(
-                &self.score.to_string(),
-                self.bird.position.0,
-                self.bird.position.1,
-                BIRD,
-                BIRD,
-                Align::Center,
-            );
-        } else {
-            window.draw_welcome(
+        window.draw_background(Color::Background);
+        match self.scene {
+            Scene::Welcome => window.draw_welcome(
                 "Bird",
                 &[
                     &["PRESS ENTER", "for play"],
                     &["PRESS ESC", "for exit"],
                     &["PRESS UP", "for fly"],
+                    &[
+                        "PRESS TAB",
+                        &format!("for level: {}", self.difficulty.label()),
+                    ],
+                    &[
+                        "PRESS M",
+                        &match (&self.control, self.brain.ready()) {
+                            (Control::Trained, false) => {
+                                String::from("for pilot: no trained brain, run --train-bird")
+                            }
+                            (control, _) => format!("for pilot: {}", control.label()),
+                        },
+                    ],
+                    &[
+                        "PRESS G",
+                        match self.race {
+                            true => "for ghost: Race",
+                            false => "for ghost: Off",
+                        },
+                    ],
                 ],
-            );
+            ),
+            Scene::Ready => {
+                self.draw_game();
+                window.draw_overlay("Get Ready", "<UP>: flap   <ESC>: Menu", Color::Foreground);
+            }
+            Scene::Playing => self.draw_game(),
+            Scene::Paused => {
+                self.draw_game();
+                window.draw_overlay("Paused", "<ENTER>: Resume   <ESC>: Menu", Color::Foreground);
+            }
+            Scene::GameOver => {
+                self.draw_game();
+                window.draw_overlay(
+                    "Game Over",
+                    &format!(
+                        "Score: {}   Best: {}   <ENTER>: Retry   <ESC>: Menu",
+                        self.world.score,
+                        self.best()
+                    ),
+                    Color::Foreground,
+                );
+            }
         }
     }
 }
-
-fn check_collision_circle_rec_manual(
-    position: (i32, i32),
-    size: i32,
-    rec: (i32, i32, i32, i32),
-) -> bool {
-    // Find the closest point on the rectangle to the center of the circle
-    let radius = size / 2;
-    let center = (position.0 + radius, position.1 + radius);
-    let closest_x = center.0.max(rec.0).min(rec.0 + rec.2);
-    let closest_y = center.1.max(rec.1).min(rec.1 + rec.3);
-
-    // Calculate the distance between the closest point and the circle's center
-    let distance_x = center.0 - closest_x;
-    let distance_y = center.1 - closest_y;
-    let distance_squared = (distance_x * distance_x) + (distance_y * distance_y);
-
-    // If the distance squared is less than the circle's radius squared, there is a collision
-    distance_squared < (radius * radius)
-}
@@ -0,0 +1,391 @@
+use crate::gui::*;
+use crate::models::breakout::*;
+use crate::models::pong::{Ball, Field, MAX_STEP, Paddle};
+use rand::RngExt;
+
+#[derive(Default)]
+pub struct Model {
+    path: String,
+    scene: Scene,
+    field: Field,
+    width: f32,
+    height: f32,
+    paddle: Paddle,
+    balls: Vec<Ball>,
+    // ball resting on the paddle until launched
+    held: bool,
+    bricks: Vec<Brick>,
+    drops: Vec<Drop>,
+    bolts: Vec<Bolt>,
+    levels: Vec<String>,
+    level: usize,
+    lives: i32,
+    score: i32,
+    best: i32,
+    // seconds left on each timed power-up
+    wide: f32,
+    slow: f32,
+    laser: f32,
+}
+
+impl Model {
+    fn start(&mut self) {
+        self.level = 0;
+        self.lives = LIVES;
+        self.score = 0;
+        self.build();
+    }
+    fn build(&mut self) {
+        let level = self.levels.get(self.level).map_or("", String::as_str);
+        self.bricks = layout(level, self.width);
+        self.serve();
+        self.scene = Scene::Playing;
+    }
+    // a fresh ball on the paddle, power-ups gone
+    fn serve(&mut self) {
+        self.paddle.resize(WIDTH * 2);
+        self.balls = vec![Ball::default()];
+        self.held = true;
+        self.drops.clear();
+        self.bolts.clear();
+        (self.wide, self.slow, self.laser) = (0.0, 0.0, 0.0);
+    }
+    // Space, Up or a click: launch a held ball or fire the laser
+    fn action(&mut self) {
+        if self.held {
+            self.held = false;
+            for ball in &mut self.balls {
+                ball.launch();
+                if self.slow > 0.0 {
+                    ball.scale(SLOW);
+                }
+            }
+        } else if self.laser > 0.0 {
+            let (x, y, w, _) = self.paddle.draw();
+            self.bolts.push(Bolt((x + PAD) as f32, y as f32));
+            self.bolts.push(Bolt((x + w - PAD) as f32, y as f32));
+        }
+    }
+    fn power(&mut self, power: Power) {
+        match power {
+            Power::Wide => {
+                if self.wide <= 0.0 {
+                    self.paddle.resize((WIDTH as f32 * 2.0 * WIDE) as i32);
+                }
+                self.wide = power.duration();
+            }
+            Power::Multi => {
+                let mut balls = Vec::new();
+                for ball in &self.balls {
+                    balls.push(ball.split(0.4));
+                    balls.push(ball.split(-0.4));
+                }
+                self.balls.extend(balls);
+                self.balls.truncate(MAX_BALLS);
+            }
+            Power::Slow => {
+                if self.slow <= 0.0 && !self.held {
+                    for ball in &mut self.balls {
+                        ball.scale(SLOW);
+                    }
+                }
+                self.slow = power.duration();
+            }
+            Power::Laser => self.laser = power.duration(),
+        }
+    }
+    fn timers(&mut self, dt: f32) {
+        if self.wide > 0.0 {
+            self.wide -= dt;
+            if self.wide <= 0.0 {
+                self.paddle.resize(WIDTH * 2);
+            }
+        }
+        if self.slow > 0.0 {
+            self.slow -= dt;
+            if self.slow <= 0.0 && !self.held {
+                for ball in &mut self.balls {
+                    ball.scale(1.0 / SLOW);
+                }
+            }
+        }
+        self.laser -= dt;
+    }
+    // damage brick `index`, dropping a power-up now and then when it breaks
+    fn strike(&mut self, index: usize) {
+        if self.bricks[index].hit() {
+            let brick = self.bricks.remove(index);
+            self.score += 10;
+            if rand::rng().random_bool(DROP_CHANCE) {
+                let (x, y, w, _) = brick.rect;
+                self.drops.push(Drop {
+                    pos: (x + (w - Drop::SIZE.0) / 2.0, y),
+                    power: Power::random(),
+                });
+            }
+        }
+    }
+    fn draw_game(&self, window: &Window) {
+        for brick in &self.bricks {
+            let (x, y, w, h) = brick.rect;
+            let color = match brick.hits {
+                None => Color::Inactive,
+                Some(1) => Color::Green,
+                Some(2) => Color::Yellow,
+                Some(_) => Color::Red,
+            };
+            window.draw_rect(x as i32, y as i32, w as i32, h as i32, 4, color);
+        }
+
+        for drop in &self.drops {
+            let (x, y) = (drop.pos.0 as i32, drop.pos.1 as i32);
+            let (w, h) = (Drop::SIZE.0 as i32, Drop::SIZE.1 as i32);
+            window.draw_rect(x, y, w, h, h / 2, Color::Selection);
+            draw::set_font(Font::CourierBold, 14);
+            draw::set_draw_color(Color::Background2);
+            draw::draw_text2(drop.power.label(), x, y, w, h, Align::Center);
+        }
+
+        draw::set_draw_color(Color::Red);
+        draw::set_line_style(draw::LineStyle::Solid, 3);
+        for bolt in &self.bolts {
+            let (x, y) = (bolt.0 as i32, bolt.1 as i32);
+            draw::draw_line(x, y, x, y + Bolt::LENGTH as i32);
+        }
+        draw::set_line_style(draw::LineStyle::Solid, 0);
+
+        let (x, y, w, h) = self.paddle.draw();
+        let color = match self.laser > 0.0 {
+            true => Color::Red,
+            false => Color::Inactive,
+        };
+        draw::draw_rect_fill(x, y, w, h, color);
+        for ball in &self.balls {
+            let (x, y, d) = ball.draw();
+            draw::draw_circle_fill(x, y, d, Color::Selection);
+        }
+
+        draw::set_font(Font::CourierBold, 18);
+        draw::set_draw_color(Color::Foreground);
+        draw::draw_text2(
+            &format!(
+                "Level {}   Score {}   Lives {}",
+                self.level + 1,
+                self.score,
+                self.lives
+            ),
+            0,
+            0,
+            window.w(),
+            HEIGHT,
+            Align::Center,
+        );
+    }
+}
+
+impl Console for Model {
+    fn load(&mut self, path: &str) {
+        self.path = path.to_string();
+        for (key, value) in load_lines(path) {
+            if key == "best" {
+                self.best = value.parse().unwrap_or_default();
+            }
+        }
+        self.levels = levels(&format!("{path}.levels"));
+    }
+    fn exit(&self, path: &str) {
+        save_lines(path, &[("best", self.best.to_string())]);
+    }
+    fn handle(&mut self, window: &mut Window, event: Event) -> bool {
+        match event {
+            Event::Focus => true,
+            Event::Resize => {
+                self.field.update((window.w(), window.h()));
+                (self.width, self.height) = (window.w() as f32, window.h() as f32);
+                self.paddle.set_y(window.h());
+                self.scene = Scene::Welcome;
+                true
+            }
+            Event::KeyDown => {
+                const LEFT: Key = Key::from_char('a');
+                const RIGHT: Key = Key::from_char('d');
+                const SPACE: Key = Key::from_char(' ');
+                match event_key() {
+                    Key::Escape => match self.scene {
+                        Scene::Welcome => {
+                            self.exit(&self.path);
+                            crate::Model::connect(window);
+                        }
+                        _ => self.scene = Scene::Welcome,
+                    },
+                    Key::Enter => match self.scene {
+                        Scene::Welcome | Scene::GameOver | Scene::Victory => self.start(),
+                        Scene::Cleared => self.build(),
+                        Scene::Playing => {}
+                    },
+                    Key::Left | LEFT => {
+                        if let Scene::Playing = self.scene {
+                            self.paddle.left();
+                        }
+                    }
+                    Key::Right | RIGHT => {
+                        if let Scene::Playing = self.scene {
+                            self.paddle.right();
+                        }
+                    }
+                    Key::Up | SPACE => {
+                        if let Scene::Playing = self.scene {
+                            self.action();
+                        }
+                    }
+                    _ => return false,
+                }
+                true
+            }
+            Event::Push => {
+                if let Scene::Playing = self.scene {
+                    self.action();
+                }
+                true
+            }
+            Event::Move => {
+                draw::set_cursor(Cursor::None);
+                let (_, _, w, _) = self.paddle.draw();
+                let x = event_coords().0 - w / 2;
+                if let Scene::Playing = self.scene
+                    && (PAD..window.width() - PAD - w).contains(&x)
+                {
+                    self.paddle.set_x(x);
+                };
+                true
+            }
+            Event::Leave => {
+                draw::set_cursor(Cursor::Arrow);
+                true
+            }
+            _ => false,
+        }
+    }
+    fn update(&mut self, dt: f32) {
+        let Scene::Playing = self.scene else {
+            return;
+        };
+        let dt = dt.min(MAX_STEP);
+        self.timers(dt);
+        self.paddle.track(dt);
+
+        if self.held {
+            let paddle = &self.paddle;
+            self.balls.iter_mut().for_each(|ball| ball.rest_on(paddle));
+        }
+
+        // small steps so a fast ball cannot pass through a brick, every strike
+        // lands at once so later balls and bolts see the bricks it left
+        if !self.held {
+            let mut balls = std::mem::take(&mut self.balls);
+            balls.retain_mut(|ball| {
+                let steps = (ball.speed() * dt / Ball::RADIUS).ceil().max(1.0);
+                for _ in 0..steps as usize {
+                    let from = ball.step(dt / steps);
+                    ball.check_paddle(&self.paddle, from, false);
+                    if let Some(index) = self
+                        .bricks
+                        .iter()
+                        .position(|b| ball.check_rect(b.rect, from))
+                    {
+                        self.strike(index);
+                    }
+                    if ball.check_field(&self.field) {
+                        return false;
+                    }
+                }
+                true
+            });
+            self.balls = balls;
+        }
+
+        for bolt in &mut self.bolts {
+            bolt.update(dt);
+        }
+        let mut bolts = std::mem::take(&mut self.bolts);
+        bolts.retain(
+            |bolt| match self.bricks.iter().position(|brick| bolt.hits(brick.rect)) {
+                Some(index) => {
+                    self.strike(index);
+                    false
+                }
+                None => bolt.1 + Bolt::LENGTH > 0.0,
+            },
+        );
+        self.bolts = bolts;
+
+        let rect = self.paddle.draw();
+        let mut caught = Vec::new();
+        self.drops.retain_mut(|drop| {
+            drop.update(dt);
+            if drop.caught(rect) {
+                caught.push(drop.power);
+                return false;
+            }
+            drop.pos.1 < self.height
+        });
+        for power in caught {
+            self.power(power);
+        }
+
+        if self.balls.is_empty() {
+            self.lives -= 1;
+            match self.lives > 0 {
+                true => self.serve(),
+                false => self.scene = Scene::GameOver,
+            }
+        }
+        if self.bricks.iter().all(|brick| brick.hits.is_none()) {
+            self.score += 100 * self.lives;
+            self.level += 1;
+            self.scene = match self.level < self.levels.len() {
+                true => Scene::Cleared,
+                false => Scene::Victory,
+            };
+        }
+        self.best = self.best.max(self.score);
+    }
+    fn draw(&self, window: &mut Window) {
+        window.draw_background(Color::Background);
+        match self.scene {
+            Scene::Welcome => window.draw_welcome(
+                "Breakout",
+                &[
+                    &["PRESS ENTER", "for play"],
+                    &["PRESS ESC", "for exit"],
+                    &["PRESS SPACE", "for launch and laser"],
+                    &["BEST", &self.best.to_string()],
+                ],
+            ),
+            Scene::Playing => self.draw_game(window),
+            Scene::Cleared => {
+                self.draw_game(window);
+                window.draw_overlay(
+                    &format!("Level {} Cleared", self.level),
+                    "<ENTER>: Next level   <ESC>: Menu",
+                    Color::Foreground,
+                );
+            }
+            Scene::GameOver | Scene::Victory => {
+                self.draw_game(window);
+                let title = match self.scene {
+                    Scene::Victory => "You Win",
+                    _ => "Game Over",
+                };
+                window.draw_overlay(
+                    title,
+                    &format!(
+                        "Score: {}   Best: {}   <ENTER>: Retry   <ESC>: Menu",
+                        self.score, self.best
+                    ),
+                    Color::Foreground,
+                );
+            }
+        }
+    }
+}
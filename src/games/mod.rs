@@ -1,4 +1,5 @@
 pub mod bird;
+pub mod breakout;
 pub mod pong;
 pub mod runner;
 pub mod snake;
@@ -3,62 +3,435 @@ use crate::models::pong::*;
 
 #[derive(Default)]
 pub struct Model {
+    path: String,
+    mode: Mode,
+    difficulty: Difficulty,
     field: Field,
     paddle: Paddle,
-    ball: Ball,
+    balls: Vec<Ball>,
     score: Score,
-    play: bool,
+    scene: Scene,
+    spin: bool,
+    rules: Rules,
+    arena: Arena,
+    // seconds until the next multiball event
+    multiball: f32,
+    left: Paddle,
+    right: Paddle,
+    cpu: Cpu,
+    // paddle keys held down as (up, down), left side then right side
+    keys: [(bool, bool); 2],
+    target: usize,
+    game: Match,
+    serve: f32,
+    // squash: balls left, ball waiting on the paddle, pause after a lost ball
+    lives: i32,
+    held: bool,
+    lost: f32,
+    rally: i32,
+    longest: i32,
+    best: i32,
+}
+
+impl Model {
+    fn start(&mut self, window: &Window) {
+        self.arena = Arena::new(&self.rules, &self.field);
+        self.multiball = MULTIBALL;
+        self.balls = vec![Ball::default()];
+        match self.mode {
+            Mode::Squash => {
+                self.score = Score::default();
+                // full length first, placing a shrunken paddle would leave
+                // the grown one partly inside the wall
+                self.paddle.resize(self.rules.length(0));
+                self.paddle.set_x(PAD);
+                self.paddle.track(0.0);
+                self.balls[0].rest_on(&self.paddle);
+                (self.lives, self.held, self.lost) = (LIVES, true, 0.0);
+                (self.rally, self.longest) = (0, 0);
+            }
+            Mode::Classic | Mode::Versus => {
+                self.left = Paddle::vertical();
+                self.right = Paddle::vertical();
+                self.left.set_x(PAD);
+                self.right.set_x(window.w() - PAD - HEIGHT);
+                self.left.center(window.h() / 2, &self.field);
+                self.right.center(window.h() / 2, &self.field);
+                self.left.track(0.0);
+                self.right.track(0.0);
+                self.cpu = Cpu::default();
+                self.keys = Default::default();
+                self.game = Match::new(TARGETS[self.target]);
+                self.balls[0].serve(&self.field, Side::Left);
+                self.serve = SERVE_DELAY;
+            }
+        }
+        self.scene = Scene::Playing;
+    }
+    // every so often the first ball splits in three
+    fn multiball(&mut self, dt: f32) {
+        if !self.rules.multiball || self.balls.is_empty() {
+            return;
+        }
+        self.multiball -= dt;
+        if self.multiball <= 0.0 {
+            self.multiball = MULTIBALL;
+            let ball = &self.balls[0];
+            let split = [ball.split(0.5), ball.split(-0.5)];
+            self.balls.extend(split);
+            self.balls.truncate(MAX_BALLS);
+        }
+    }
+    fn squash(&mut self, dt: f32) {
+        self.paddle.track(dt);
+        self.paddle.resize(self.rules.length(self.score.0));
+        self.arena.update(&self.field, dt);
+        if self.lost > 0.0 {
+            self.lost -= dt;
+            if self.lost <= 0.0 {
+                match self.lives > 0 {
+                    true => {
+                        self.held = true;
+                        self.balls = vec![Ball::default()];
+                    }
+                    false => {
+                        self.best = self.best.max(self.score.0);
+                        self.scene = Scene::GameOver;
+                    }
+                }
+            }
+            return;
+        }
+        if self.held {
+            self.balls[0].rest_on(&self.paddle);
+            return;
+        }
+        self.multiball(dt);
+        let mut hits = 0;
+        let (paddle, field, arena, spin) = (&self.paddle, &self.field, &self.arena, self.spin);
+        self.balls.retain_mut(|ball| {
+            let from = ball.step(dt);
+            arena.apply(ball, from, dt);
+            if ball.check_paddle(paddle, from, spin) {
+                hits += 1;
+            }
+            !ball.check_field(field)
+        });
+        for _ in 0..hits {
+            self.score.inc();
+            self.rally += 1;
+            self.longest = self.longest.max(self.rally);
+        }
+        // a life goes with the last ball in play
+        if self.balls.is_empty() {
+            self.lives -= 1;
+            self.rally = 0;
+            self.lost = LOST_PAUSE;
+        }
+        self.score.update();
+    }
+    // Paddle key pressed or let go: in versus W/S hold the left paddle and the
+    // arrows the right one, otherwise all of them hold the player's paddle.
+    // False for any other key.
+    fn press(&mut self, key: Key, down: bool) -> bool {
+        const UP: Key = Key::from_char('w');
+        const DOWN: Key = Key::from_char('s');
+        let side = match (self.mode, key) {
+            (Mode::Versus, Key::Up | Key::Down) => 1,
+            _ => 0,
+        };
+        match key {
+            Key::Up | UP => self.keys[side].0 = down,
+            Key::Down | DOWN => self.keys[side].1 = down,
+            _ => return false,
+        }
+        true
+    }
+    // the player sends a ball resting on the squash paddle
+    fn serve(&mut self) {
+        if self.held {
+            self.held = false;
+            self.balls[0].launch();
+        }
+    }
+    // both two-paddle modes, the CPU plays the right side unless in versus
+    fn classic(&mut self, dt: f32) {
+        self.game.time += dt;
+        let length = self.rules.length(self.game.points.0 + self.game.points.1);
+        self.left.resize(length);
+        self.right.resize(length);
+        for (paddle, (up, down)) in [
+            (&mut self.left, self.keys[0]),
+            (&mut self.right, self.keys[1]),
+        ] {
+            paddle.slide(down as i32 - up as i32, &self.field, dt);
+        }
+        self.left.track(dt);
+        if self.mode == Mode::Classic {
+            self.cpu.update(
+                &self.difficulty,
+                &self.balls,
+                &mut self.right,
+                &self.field,
+                dt,
+            );
+        }
+        self.right.track(dt);
+        self.arena.update(&self.field, dt);
+        if self.serve > 0.0 {
+            self.serve -= dt;
+            return;
+        }
+        self.multiball(dt);
+        let (mut hits, mut conceded) = (Vec::new(), Vec::new());
+        let (left, right, field, arena, spin) =
+            (&self.left, &self.right, &self.field, &self.arena, self.spin);
+        self.balls.retain_mut(|ball| {
+            let from = ball.step(dt);
+            arena.apply(ball, from, dt);
+            if ball.check_paddle(left, from, spin) {
+                hits.push(Side::Left);
+            } else if ball.check_paddle(right, from, spin) {
+                hits.push(Side::Right);
+            }
+            match ball.check_goal(field) {
+                Some(side) => {
+                    conceded.push(side);
+                    false
+                }
+                None => true,
+            }
+        });
+        for side in hits {
+            self.game.hit(side);
+        }
+        for &side in &conceded {
+            self.game.point(side);
+            if self.game.winner().is_some() {
+                self.scene = Scene::Summary;
+                return;
+            }
+        }
+        // the side that conceded the last ball receives the next serve
+        if let (true, Some(&side)) = (self.balls.is_empty(), conceded.last()) {
+            let mut ball = Ball::default();
+            ball.serve(&self.field, side);
+            self.balls.push(ball);
+            self.serve = SERVE_DELAY;
+        }
+    }
+    fn draw_arena(&self) {
+        for obstacle in &self.arena.obstacles {
+            let (x, y, w, h) = obstacle.rect;
+            draw::draw_rect_fill(x as i32, y as i32, w as i32, h as i32, Color::Foreground);
+        }
+        draw::set_draw_color(Color::Inactive);
+        for (x, y) in &self.arena.wells {
+            for r in [PAD, HEIGHT, HEIGHT * 2] {
+                draw::draw_arc(*x as i32 - r, *y as i32 - r, 2 * r, 2 * r, 0.0, 360.0);
+            }
+        }
+    }
+    fn draw_squash(&self, window: &Window) {
+        self.draw_arena();
+        let paddle = self.paddle.draw();
+        let score = self.score.draw();
+        for life in 0..self.lives {
+            draw::draw_circle_fill(PAD + life * HEIGHT, PAD, HEIGHT - PAD, Color::Selection);
+        }
+        draw::draw_rect_fill(paddle.0, paddle.1, paddle.2, paddle.3, Color::Inactive);
+        if self.lost > 0.0 {
+            window.draw_overlay("Ball Lost", "", Color::Foreground);
+        } else if self.held {
+            window.draw_overlay("", "<SPACE> or click: serve", Color::Foreground);
+        }
+        for ball in &self.balls {
+            let ball = ball.draw();
+            draw::draw_circle_fill(ball.0, ball.1, ball.2, Color::Selection);
+            draw::set_draw_color(Color::Background2);
+            draw::draw_text2(
+                &score.1.to_string(),
+                ball.0,
+                ball.1,
+                ball.2,
+                ball.2,
+                Align::Center,
+            );
+        }
+        draw::set_draw_color(Color::Background2);
+        draw::draw_text2(
+            &score.0.to_string(),
+            paddle.0,
+            paddle.1,
+            paddle.2,
+            paddle.3,
+            Align::Center,
+        );
+    }
+    fn draw_over(&self, window: &Window) {
+        window.draw_welcome(
+            "Game Over",
+            &[
+                &["HITS", &self.score.0.to_string()],
+                &["LONGEST RALLY", &self.longest.to_string()],
+                &["BEST", &self.best.to_string()],
+                &["PRESS ENTER", "for retry"],
+                &["PRESS ESC", "for menu"],
+            ],
+        );
+    }
+    fn draw_summary(&self, window: &Window) {
+        let game = &self.game;
+        let winner = game.winner().map_or("", |side| self.mode.name(side));
+        let (left, right) = (self.mode.name(Side::Left), self.mode.name(Side::Right));
+        let score = format!("{left} {} - {} {right}", game.points.0, game.points.1);
+        let hits = format!("{left} {} - {} {right}", game.hits.0, game.hits.1);
+        let time = format!("{}:{:02}", game.time as i32 / 60, game.time as i32 % 60);
+        window.draw_welcome(
+            &match self.mode {
+                Mode::Versus => format!("{winner} Wins"),
+                _ if winner == "You" => "You Win".to_string(),
+                _ => "CPU Wins".to_string(),
+            },
+            &[
+                &["SCORE", &score],
+                &["HITS", &hits],
+                &["LONGEST RALLY", &game.longest.to_string()],
+                &["MATCH TIME", &time],
+                &["PRESS ENTER", "for rematch"],
+                &["PRESS ESC", "for menu"],
+            ],
+        );
+    }
+    fn draw_classic(&self, window: &Window) {
+        let (w, h) = (window.w(), window.h());
+        for y in (0..h).step_by(HEIGHT as usize * 2) {
+            draw::draw_rect_fill(w / 2 - PAD / 4, y, PAD / 2, HEIGHT, Color::Inactive);
+        }
+        draw::set_font(Font::CourierBold, 48);
+        draw::set_draw_color(Color::Foreground);
+        for (x, points) in [(0, self.game.points.0), (w / 2, self.game.points.1)] {
+            draw::draw_text2(
+                &points.to_string(),
+                x,
+                PAD,
+                w / 2,
+                HEIGHT * 2,
+                Align::Center,
+            );
+        }
+        if self.game.deuce() {
+            let call = match self.game.leader() {
+                Some(side) => format!("Advantage {}", self.mode.name(side)),
+                None => "Deuce".to_string(),
+            };
+            draw::set_font(Font::CourierBold, 24);
+            draw::draw_text2(&call, 0, PAD + HEIGHT * 2, w, HEIGHT, Align::Center);
+        }
+        self.draw_arena();
+        for paddle in [&self.left, &self.right] {
+            let (x, y, w, h) = paddle.draw();
+            draw::draw_rect_fill(x, y, w, h, Color::Inactive);
+        }
+        for ball in &self.balls {
+            let ball = ball.draw();
+            draw::draw_circle_fill(ball.0, ball.1, ball.2, Color::Selection);
+        }
+    }
 }
 
 impl Console for Model {
-    fn load(&mut self, _path: &str) {}
-    fn exit(&self, _path: &str) {}
+    fn load(&mut self, path: &str) {
+        self.path = path.to_string();
+        for (key, value) in load_lines(path) {
+            if key == "best" {
+                self.best = value.parse().unwrap_or_default();
+            }
+        }
+    }
+    fn exit(&self, path: &str) {
+        save_lines(path, &[("best", self.best.to_string())]);
+    }
     fn handle(&mut self, window: &mut Window, event: Event) -> bool {
         match event {
             Event::Focus => true,
             Event::Resize => {
                 self.field.update((window.w(), window.h()));
                 self.paddle.set_y(window.h());
-                self.play = false;
+                self.scene = Scene::Welcome;
                 true
             }
             Event::KeyDown => {
                 const LEFT: Key = Key::from_char('a');
                 const RIGHT: Key = Key::from_char('d');
-                match event_key() {
-                    Key::Escape => match self.play {
-                        true => self.play = false,
-                        false => crate::Model::connect(window),
-                    },
-                    Key::Enter => {
-                        if !self.play {
-                            self.score = Score::default();
-                            self.ball = Ball::default();
-                            self.paddle.set_x(PAD);
-                            self.play = true;
-                        }
+                const UP: Key = Key::from_char('w');
+                const DOWN: Key = Key::from_char('s');
+                const SPACE: Key = Key::from_char(' ');
+                const SPIN: Key = Key::from_char('p');
+                const TARGET: Key = Key::from_char('n');
+                const MULTIBALL: Key = Key::from_char('1');
+                const OBSTACLES: Key = Key::from_char('2');
+                const SHRINK: Key = Key::from_char('3');
+                const WELLS: Key = Key::from_char('4');
+                let Scene::Playing = self.scene else {
+                    match event_key() {
+                        Key::Escape => match self.scene {
+                            Scene::Welcome => {
+                                self.exit(&self.path);
+                                crate::Model::connect(window);
+                            }
+                            _ => self.scene = Scene::Welcome,
+                        },
+                        Key::Enter => self.start(window),
+                        Key::Tab => self.mode = self.mode.switch(),
+                        SPACE => self.difficulty = self.difficulty.switch(),
+                        SPIN => self.spin = !self.spin,
+                        TARGET => self.target = (self.target + 1) % TARGETS.len(),
+                        MULTIBALL => self.rules.multiball = !self.rules.multiball,
+                        OBSTACLES => self.rules.obstacles = !self.rules.obstacles,
+                        SHRINK => self.rules.shrink = !self.rules.shrink,
+                        WELLS => self.rules.wells = !self.rules.wells,
+                        _ => return false,
                     }
-                    Key::Left | LEFT => {
-                        if self.play {
-                            self.paddle.left();
-                        }
-                    }
-                    Key::Right | RIGHT => {
-                        if self.play {
-                            self.paddle.right();
-                        }
+                    window.redraw();
+                    return true;
+                };
+                let key = event_key();
+                match (self.mode, key) {
+                    (Mode::Squash, Key::Up | UP | SPACE) => self.serve(),
+                    (Mode::Squash, Key::Left | LEFT) => self.paddle.left(),
+                    (Mode::Squash, Key::Right | RIGHT) => self.paddle.right(),
+                    (Mode::Classic | Mode::Versus, Key::Up | UP | Key::Down | DOWN) => {
+                        return self.press(key, true);
                     }
+                    (_, Key::Escape) => self.scene = Scene::Welcome,
                     _ => return false,
                 }
                 true
             }
+            Event::KeyUp => self.press(event_key(), false),
+            Event::Push => {
+                if let (Scene::Playing, Mode::Squash) = (&self.scene, self.mode) {
+                    self.serve();
+                }
+                true
+            }
             Event::Move => {
                 draw::set_cursor(Cursor::None);
-                if self.play
-                    && (PAD + WIDTH..window.width() - PAD - WIDTH).contains(&event_coords().0)
-                {
-                    self.paddle.set_x(event_coords().0 - WIDTH);
-                };
+                let (x, y) = event_coords();
+                if let Scene::Playing = self.scene {
+                    match self.mode {
+                        Mode::Squash => {
+                            let (_, _, w, _) = self.paddle.draw();
+                            if (PAD + w / 2..window.width() - PAD - w / 2).contains(&x) {
+                                self.paddle.set_x(x - w / 2);
+                            };
+                        }
+                        Mode::Classic => self.left.center(y, &self.field),
+                        // the mouse stands in for the arrow keys
+                        Mode::Versus => self.right.center(y, &self.field),
+                    }
+                }
                 true
             }
             Event::Leave => {
@@ -68,54 +441,61 @@ impl Console for Model {
             _ => false,
         }
     }
-    fn update(&mut self, _dt: f32) {
-        if !self.play {
+    fn update(&mut self, dt: f32) {
+        let Scene::Playing = self.scene else {
             return;
+        };
+        let dt = dt.min(MAX_STEP);
+        match self.mode {
+            Mode::Squash => self.squash(dt),
+            Mode::Classic | Mode::Versus => self.classic(dt),
         }
-        for _ in 0..(PAD + 5 * self.score.1) {
-            self.ball.step();
-            if self.ball.check_field(&self.field) {
-                self.score.dec();
-            }
-            if self.ball.check_paddle(&self.paddle) {
-                self.score.inc();
-            }
-            if self.score.0 < 0 {
-                self.play = false;
-            };
-        }
-        self.score.update();
     }
     fn draw(&self, window: &mut Window) {
-        let paddle = self.paddle.draw();
-        let ball = self.ball.draw();
-        let score = self.score.draw();
         draw::draw_rect_fill(0, 0, window.width(), window.height(), Color::Background);
-        if self.play {
-            draw::draw_circle_fill(ball.0, ball.1, ball.2, Color::Selection);
-            draw::draw_rect_fill(paddle.0, paddle.1, paddle.2, paddle.3, Color::Inactive);
-            draw::set_draw_color(Color::Background2);
-            draw::draw_text2(
-                &score.1.to_string(),
-                ball.0,
-                ball.1,
-                ball.2,
-                ball.2,
-                Align::Center,
-            );
-            draw::draw_text2(
-                &score.0.to_string(),
-                paddle.0,
-                paddle.1,
-                paddle.2,
-                paddle.3,
-                Align::Center,
-            );
-        } else {
-            window.draw_welcome(
-                "Pong",
-                &[&["PRESS ENTER", "for play"], &["PRESS ESC", "for exit"]],
-            );
+        match self.scene {
+            Scene::Playing => match self.mode {
+                Mode::Squash => self.draw_squash(window),
+                Mode::Classic | Mode::Versus => self.draw_classic(window),
+            },
+            Scene::Summary => self.draw_summary(window),
+            Scene::GameOver => self.draw_over(window),
+            Scene::Welcome => {
+                let mut menu: Vec<&[&str]> =
+                    vec![&["PRESS ENTER", "for play"], &["PRESS ESC", "for exit"]];
+                let mode = format!("for mode: {}", self.mode.label());
+                let level = format!("for CPU: {}", self.difficulty.label());
+                let spin = format!("for spin: {}", if self.spin { "On" } else { "Off" });
+                let target = format!("for points: {}", TARGETS[self.target]);
+                let best = self.best.to_string();
+                let mark = |on: bool| if on { "+" } else { "-" };
+                let rules = format!(
+                    "for rules: {}multiball {}obstacles {}shrink {}wells",
+                    mark(self.rules.multiball),
+                    mark(self.rules.obstacles),
+                    mark(self.rules.shrink),
+                    mark(self.rules.wells),
+                );
+                let rows = [
+                    ["PRESS TAB", mode.as_str()],
+                    ["PRESS SPACE", level.as_str()],
+                    ["PRESS P", spin.as_str()],
+                    ["PRESS N", target.as_str()],
+                    ["BEST", best.as_str()],
+                    ["PRESS 1-4", rules.as_str()],
+                ];
+                menu.push(&rows[0]);
+                if self.mode == Mode::Classic {
+                    menu.push(&rows[1]);
+                }
+                menu.push(&rows[2]);
+                match self.mode {
+                    Mode::Squash => menu.push(&rows[4]),
+                    _ => menu.push(&rows[3]),
+                }
+                menu.push(&rows[5]);
+                window.draw_welcome("Pong", &menu);
+            }
         }
     }
 }
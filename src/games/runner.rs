@@ -1,8 +1,84 @@
 use crate::gui::*;
 use crate::models::runner::*;
+use fltk::image::SharedImage;
+use std::collections::{HashMap, VecDeque};
 const PADDING: i32 = 34;
+// Built-in sprite sheets, frames side by side, painted in the two colours
+// below. A `<name>.png` or `<name>.svg` in the sprites directory with the
+// same frames replaces one.
+const DINO: &str = include_str!("../../assets/runner/dino.svg");
+const DUCK: &str = include_str!("../../assets/runner/duck.svg");
+const CACTUS: &str = include_str!("../../assets/runner/cactus.svg");
+const PTERO: &str = include_str!("../../assets/runner/ptero.svg");
+const INK: &str = "#535353";
+const SKY: &str = "#f7f7f7";
+
+// A sheet scaled once to the hitbox its frames fill.
+struct Sheet {
+    image: SharedImage,
+    frames: usize,
+    size: (i32, i32),
+}
+
+impl Sheet {
+    fn new(image: &SharedImage, frames: usize, (w, h): (f32, f32)) -> Self {
+        let size = (w as i32, h as i32);
+        Self {
+            image: image.copy_sized(size.0 * frames as i32, size.1),
+            frames,
+            size,
+        }
+    }
+    fn draw(&self, frame: usize, x: f32, y: f32) {
+        let (w, h) = self.size;
+        let cx = w * (frame % self.frames) as i32;
+        self.image.clone().draw_ext(x as i32, y as i32, w, h, cx, 0);
+    }
+}
+
+struct Sprites {
+    dino: Sheet,
+    duck: Sheet,
+    cactus: Sheet,
+    big_cactus: Sheet,
+    ptero: Sheet,
+}
+
+impl Sprites {
+    fn new(dir: &str, (sky, ink): (Color, Color)) -> Option<Self> {
+        let load = |name: &str, data: &str| {
+            ["png", "svg"]
+                .iter()
+                .find_map(|ext| SharedImage::load(format!("{dir}/{name}.{ext}")).ok())
+                .or_else(|| {
+                    let data = data
+                        .replace(INK, &ink.to_hex_str())
+                        .replace(SKY, &sky.to_hex_str());
+                    SharedImage::from_image(&SvgImage::from_data(&data).ok()?).ok()
+                })
+        };
+        let mut dino = Dino::default();
+        let stand = (dino.w(), dino.height());
+        dino.ducking = true;
+        let duck = (dino.w(), dino.height());
+        let cactus = load("cactus", CACTUS)?;
+        Some(Self {
+            dino: Sheet::new(&load("dino", DINO)?, 4, stand),
+            duck: Sheet::new(&load("duck", DUCK)?, 2, duck),
+            cactus: Sheet::new(&cactus, CACTUS_VARIANTS, ObstacleKind::Cactus(true).size()),
+            big_cactus: Sheet::new(&cactus, CACTUS_VARIANTS, ObstacleKind::Cactus(false).size()),
+            ptero: Sheet::new(
+                &load("ptero", PTERO)?,
+                2,
+                ObstacleKind::Pterodactyl(true).size(),
+            ),
+        })
+    }
+}
+
 #[derive(Default)]
 pub struct Model {
+    path: String,
     scene: Scene,
     difficulty: Difficulty,
     dino: Dino,
@@ -10,26 +86,77 @@ pub struct Model {
     run_speed: f32,
     distance_x: f32,
     ground_scroll: f32,
+    scenery: Scenery,
+    // 0 by day, 1 by night, in between while fading
+    night: f32,
     obstacles: Vec<Obstacle>,
     next_gap: f32,
+    // pattern pieces still to come, with the free ground after each
+    pattern: VecDeque<(ObstacleKind, f32)>,
     score: u32,
-    high_score: u32,
+    // best score per difficulty label and the latest runs, newest first
+    best: HashMap<String, u32>,
+    history: Vec<Run>,
+    // by day and by night, rectangles stand in when a sheet fails to load
+    sprites: Option<[Sprites; 2]>,
 }
 impl Console for Model {
-    fn load(&mut self, _path: &str) {}
-    fn exit(&self, _path: &str) {}
+    fn load(&mut self, path: &str) {
+        self.path = path.to_string();
+        for (key, value) in load_lines(path) {
+            match (key.as_str(), value.as_str()) {
+                ("best", value) => {
+                    if let Some((label, score)) = value.split_once(' ')
+                        && let Some(difficulty) = Difficulty::from_label(label)
+                    {
+                        self.best.insert(
+                            difficulty.label().to_string(),
+                            score.parse().unwrap_or_default(),
+                        );
+                    }
+                }
+                ("run", value) => {
+                    if let Some(run) = Run::from_line(value)
+                        && self.history.len() < HISTORY
+                    {
+                        self.history.push(run);
+                    }
+                }
+                _ => {}
+            }
+        }
+        let dir = format!("{path}.sprites");
+        self.sprites = Sprites::new(&dir, palette(0.0))
+            .zip(Sprites::new(&dir, palette(1.0)))
+            .map(|(day, night)| [day, night]);
+    }
+    fn exit(&self, path: &str) {
+        let mut save: Vec<(&str, String)> = Vec::new();
+        for (label, score) in &self.best {
+            save.push(("best", format!("{label} {score}")));
+        }
+        for run in &self.history {
+            save.push(("run", run.to_line()));
+        }
+        save_lines(path, &save);
+    }
     fn handle(&mut self, window: &mut Window, event: Event) -> bool {
         match event {
             Event::Focus => true,
             Event::Resize => {
                 self.size = (window.w(), window.h());
+                self.scenery = Scenery::new(self.size.0 as f32, self.ground_y());
                 self.scene = Scene::Welcome;
                 true
             }
             Event::KeyDown => {
+                const SPACE: Key = Key::from_char(' ');
                 match event_key() {
                     Key::Escape => match self.scene {
-                        Scene::Welcome => crate::Model::connect(window),
+                        Scene::Welcome => {
+                            self.exit(&self.path);
+                            crate::Model::connect(window);
+                        }
                         _ => self.scene = Scene::Welcome,
                     },
                     Key::Enter => match self.scene {
@@ -41,17 +168,14 @@ impl Console for Model {
                             self.scene = Scene::Playing;
                         }
                     },
-                    Key::Up => {
-                        if let Scene::Playing = self.scene
-                            && !self.dino.jumping
-                        {
-                            self.dino.jumping = true;
-                            self.dino.vy = -900.0;
+                    Key::Up | SPACE => {
+                        if let Scene::Playing = self.scene {
+                            self.dino.press();
                         }
                     }
                     Key::Down => {
                         if let Scene::Playing = self.scene {
-                            self.dino.ducking = true
+                            self.dino.duck();
                         }
                     }
                     Key::Tab => {
@@ -64,12 +188,10 @@ impl Console for Model {
                 true
             }
             Event::KeyUp => {
+                const SPACE: Key = Key::from_char(' ');
                 match event_key() {
-                    Key::Down => {
-                        if let Scene::Playing = self.scene {
-                            self.dino.ducking = false
-                        }
-                    }
+                    Key::Up | SPACE => self.dino.release(),
+                    Key::Down => self.dino.stand(),
                     _ => return false,
                 };
                 true
@@ -79,64 +201,25 @@ impl Console for Model {
     }
     fn update(&mut self, dt: f32) {
         if let Scene::Playing = self.scene {
-            let ground_top = self.ground_y() - self.dino.height();
-            if self.dino.jumping {
-                self.dino.vy += 2400.0 * dt;
-                self.dino.y += self.dino.vy * dt;
-                self.dino.jumping = self.dino.y < ground_top;
-            } else {
-                self.dino.y = ground_top;
-                self.dino.vy = 0.0;
-                self.dino.leg_timer += dt;
-                if self.dino.leg_timer >= 0.12 {
-                    self.dino.leg_timer = 0.0;
-                    self.dino.leg_state = !self.dino.leg_state;
-                }
-            }
+            let ground = self.ground_y();
+            self.dino.update(dt, ground);
             self.run_speed *= (1.0 + (0.06 * dt)).min(1.02);
             let dx = self.run_speed * dt;
             self.distance_x += dx;
-            self.ground_scroll = (self.ground_scroll + dx) % 32.0;
+            self.ground_scroll = (self.ground_scroll + dx) % GROUND_TILE;
+            self.scenery.update(dx, self.size.0 as f32, self.ground_y());
             self.score = (self.score as f32 + 120.0 * dt) as u32;
+            let night = ((self.score / DAY_LENGTH) % 2) as f32;
+            self.night += (night - self.night).clamp(-dt / FADE, dt / FADE);
             self.next_gap -= dx;
             if self.next_gap <= 0.0 {
-                let kind = match self.difficulty.ptero_enabled()
-                    && self.score > 300
-                    && rand::rng().random_bool(0.35)
-                {
-                    true => ObstacleKind::Pterodactyl(rand::rng().random_bool(0.5)),
-                    false => ObstacleKind::Cactus(rand::rng().random_bool(0.4)),
-                };
-                let (y, w, h) = match kind {
-                    ObstacleKind::Cactus(tall) => match tall {
-                        true => (self.ground_y() - 46.0, 24.0, 46.0),
-                        false => (self.ground_y() - 60.0, 36.0, 60.0),
-                    },
-                    ObstacleKind::Pterodactyl(high) => match high {
-                        true => (self.ground_y() - 48.0 - 22.0 - 24.0, 46.0, 24.0),
-                        false => (self.ground_y() - 30.0 - 10.0 - 24.0, 46.0, 24.0),
-                    },
-                };
-                let range = if let ObstacleKind::Pterodactyl(..) = kind {
-                    const PTERO_MIN_GAP: f32 = 520.0;
-                    const PTERO_MAX_GAP: f32 = 760.0;
-                    PTERO_MIN_GAP * self.difficulty.gap_scale()
-                        ..PTERO_MAX_GAP * self.difficulty.gap_scale()
-                } else {
-                    let (min, max) = self.difficulty.limit();
-                    min..max
-                };
-                self.next_gap = rand::rng().random_range(range);
-                self.obstacles.push(Obstacle {
-                    rect: ((self.size.0 - PADDING) as f32 - w, y, w, h),
-                    kind,
-                });
+                self.spawn();
             }
             for ob in &mut self.obstacles {
                 ob.rect.0 -= dx;
             }
             self.obstacles.retain(|o| o.rect.0 >= PADDING as f32);
-            if self.obstacles.iter().any(|obstacle| {
+            if let Some(obstacle) = self.obstacles.iter().find(|obstacle| {
                 rect_intersect(
                     draw::Rect::new(
                         self.dino.x() as i32,
@@ -152,23 +235,49 @@ impl Console for Model {
                     ),
                 )
             }) {
-                self.high_score = self.high_score.max(self.score);
+                let label = self.difficulty.label().to_string();
+                let best = self.best.entry(label.clone()).or_default();
+                *best = (*best).max(self.score);
+                self.history.insert(
+                    0,
+                    Run {
+                        difficulty: label,
+                        score: self.score,
+                        distance: self.distance_x as u32 / 10,
+                        cause: obstacle.kind.label().to_string(),
+                    },
+                );
+                self.history.truncate(HISTORY);
+                self.dino.dead = true;
                 self.scene = Scene::GameOver;
             }
         }
     }
     fn draw(&self, window: &mut Window) {
         window.draw_background(Color::Background);
+        let (sky, ink) = palette(self.night);
         window.draw_rect(
             PADDING,
             PADDING,
             window.w() - PADDING * 2,
             window.h() - PADDING * 2,
             15,
-            Color::Background2,
+            sky,
         );
+        draw::push_clip(
+            PADDING,
+            PADDING,
+            window.w() - PADDING * 2,
+            window.h() - PADDING * 2,
+        );
+        self.draw_scenery(window, sky, ink);
+        draw::pop_clip();
         window.draw_text(
-            &format!("Score: {:05}   High: {:05}", self.score, self.high_score),
+            &format!(
+                "Score: {:05}   High: {:05}",
+                self.score,
+                self.best.get(self.difficulty.label()).unwrap_or(&0)
+            ),
             PADDING,
             2,
             Color::Foreground,
@@ -181,21 +290,43 @@ impl Console for Model {
             window.w() - PADDING * 2,
             4,
             5,
-            Color::Foreground,
+            ink,
         );
         match self.scene {
             Scene::Playing => self.draw_game(),
-            Scene::Welcome => window.draw_welcome(
-                "T-Rex Runner",
-                &[
-                    &["PRESS <ENTER>", "for play"],
-                    &["PRESS <ESC>", "for exit"],
-                    &[
-                        "PRESS <TAB>",
-                        &format!("for level: {}", self.difficulty.label()),
-                    ],
-                ],
-            ),
+            Scene::Welcome => {
+                let level = format!("for level: {}", self.difficulty.label());
+                let best = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard]
+                    .iter()
+                    .map(|difficulty| {
+                        let label = difficulty.label();
+                        format!("{label} {}", self.best.get(label).unwrap_or(&0))
+                    })
+                    .collect::<Vec<String>>()
+                    .join("  ");
+                let recent: Vec<String> = self
+                    .history
+                    .iter()
+                    .take(RECENT)
+                    .map(|run| {
+                        format!(
+                            "{:05} {:>5}m {} ({})",
+                            run.score, run.distance, run.cause, run.difficulty
+                        )
+                    })
+                    .collect();
+                let mut menu: Vec<[&str; 2]> = vec![
+                    ["PRESS <ENTER>", "for play"],
+                    ["PRESS <ESC>", "for exit"],
+                    ["PRESS <TAB>", &level],
+                    ["BEST", &best],
+                ];
+                for (i, run) in recent.iter().enumerate() {
+                    menu.push([if i == 0 { "RECENT" } else { "" }, run]);
+                }
+                let menu: Vec<&[&str]> = menu.iter().map(|row| row.as_slice()).collect();
+                window.draw_welcome("T-Rex Runner", &menu);
+            }
             Scene::Paused => {
                 self.draw_game();
                 window.draw_overlay("Paused", "<ENTER> or <ESC>: Menu", Color::Foreground);
@@ -211,7 +342,35 @@ impl Model {
     fn ground_y(&self) -> f32 {
         (self.size.1 - PADDING) as f32 - 64.0
     }
+    fn draw_scenery(&self, window: &Window, sky: Color, ink: Color) {
+        let ground = self.ground_y() as i32;
+        let mountain = Color::color_average(ink, sky, 0.25);
+        draw::set_draw_color(mountain);
+        for (x, half, height) in &self.scenery.mountains {
+            let (x, half, height) = (*x as i32, *half as i32, *height as i32);
+            draw::draw_polygon(x - half, ground, x, ground - height, x + half, ground);
+        }
+        let cloud = Color::color_average(ink, sky, 0.12);
+        let (w, h) = (Scenery::CLOUD.0 as i32, Scenery::CLOUD.1 as i32);
+        for (x, y) in &self.scenery.clouds {
+            let (x, y) = (*x as i32, *y as i32);
+            window.draw_rect(x, y, w, h, h / 2, cloud);
+            window.draw_rect(x + w / 4, y - h / 2, w / 2, h, h / 2, cloud);
+        }
+        // pebbles and cracks below the ground line, moving with the ground
+        let tile = GROUND_TILE as i32;
+        let mut x = PADDING - self.ground_scroll as i32;
+        while x < self.size.0 - PADDING {
+            for (dx, dy, w) in [(0, 10, 8), (18, 18, 3), (26, 12, 4)] {
+                if x + dx >= PADDING {
+                    draw::draw_rect_fill(x + dx, ground + dy, w, 2, ink);
+                }
+            }
+            x += tile;
+        }
+    }
     fn setup(&mut self) {
+        self.night = 0.0;
         self.dino = Dino::default();
         self.dino.y = self.ground_y() - self.dino.height();
         self.distance_x = 0.0;
@@ -220,9 +379,66 @@ impl Model {
         const BASE_RUN_SPEED: f32 = 360.0;
         self.run_speed = BASE_RUN_SPEED * self.difficulty.speed_mul();
         self.obstacles.clear();
+        self.pattern.clear();
         self.next_gap = self.difficulty.scaled_gap(true);
     }
+    // Places the next pattern piece at the right edge, moved on by however far
+    // the ground overshot the gap, and measures the ground to the one after.
+    fn spawn(&mut self) {
+        self.refill();
+        let Some((kind, gap)) = self.pattern.pop_front() else {
+            return;
+        };
+        let (w, h) = kind.size();
+        let (y, frame) = match kind {
+            ObstacleKind::Cactus(..) => (
+                self.ground_y() - h,
+                rand::rng().random_range(0..CACTUS_VARIANTS),
+            ),
+            ObstacleKind::Pterodactyl(high) => match high {
+                true => (self.ground_y() - 48.0 - 22.0 - h, 0),
+                false => (self.ground_y() - 30.0 - 10.0 - h, 1),
+            },
+        };
+        let x = (self.size.0 - PADDING) as f32 - w + self.next_gap;
+        self.obstacles.push(Obstacle {
+            rect: (x, y, w, h),
+            kind,
+            frame,
+        });
+        self.refill();
+        let next = self.pattern.front().map_or(0.0, |(kind, _)| kind.size().0);
+        self.next_gap += gap + next;
+    }
+    fn refill(&mut self) {
+        if self.pattern.is_empty() {
+            let flying = self.difficulty.ptero_enabled() && self.score > 300;
+            self.pattern = pattern(&self.difficulty, self.run_speed, flying).into();
+        }
+    }
     fn draw_game(&self) {
+        let Some(sprites) = &self.sprites else {
+            return self.draw_boxes();
+        };
+        let sprites = &sprites[(self.night > 0.5) as usize];
+        let (x, y) = (self.dino.x(), self.dino.y);
+        match self.dino.pose() {
+            Pose::Run(leg) => sprites.dino.draw(leg, x, y),
+            Pose::Jump => sprites.dino.draw(2, x, y),
+            Pose::Dead => sprites.dino.draw(3, x, y),
+            Pose::Duck(leg) => sprites.duck.draw(leg, x, y),
+        }
+        for obstacle in &self.obstacles {
+            let sheet = match obstacle.kind {
+                ObstacleKind::Cactus(true) => &sprites.cactus,
+                ObstacleKind::Cactus(false) => &sprites.big_cactus,
+                ObstacleKind::Pterodactyl(..) => &sprites.ptero,
+            };
+            let (x, y, ..) = obstacle.rect;
+            sheet.draw(obstacle.frame(self.distance_x), x, y);
+        }
+    }
+    fn draw_boxes(&self) {
         draw::draw_rect_fill(
             self.dino.x() as i32,
             self.dino.y as i32,
@@ -262,6 +478,13 @@ impl Model {
         }
     }
 }
+// sky and ink swap places at night, both taken from the active theme
+fn palette(night: f32) -> (Color, Color) {
+    (
+        Color::color_average(Color::Foreground, Color::Background2, night),
+        Color::color_average(Color::Background2, Color::Foreground, night),
+    )
+}
 fn rect_intersect(a: draw::Rect, b: draw::Rect) -> bool {
     a.x < (b.x + b.w) && (a.x + a.w) > b.x && a.y < (b.y + b.h) && (a.y + a.h) > b.y
 }
@@ -1,38 +1,424 @@
 use crate::gui::*;
 use crate::models::snake::*;
+use std::collections::HashMap;
+
+const PLAYERS: [&str; 2] = ["Player 1", "Player 2"];
 
 #[derive(Default)]
 pub struct Model {
+    path: String,
+    size: (i32, i32),
     field: (i32, i32),
+    scene: Scene,
+    mode: Mode,
+    board: Board,
     snake: Snake,
+    rival: Snake,
     score: Score,
+    rival_score: Score,
+    wins: [u32; 2],
+    victor: Option<usize>,
+    elapsed: f32,
+    apples: i32,
+    cause: &'static str,
+    // best result per mode and board, keyed by `record()`
+    best: HashMap<String, i32>,
+    hunger: f32,
     apple: Apple,
-    play: bool,
+    apple_life: f32,
+    item: Option<Item>,
+    item_timer: f32,
+    slow: f32,
+    bot: Autopilot,
+    smooth: bool,
+    trail: [Option<(i32, i32)>; 2],
     timer: f32,
 }
 
 impl Model {
     fn set_field(&mut self) {
-        self.field = (COLS, ROWS);
+        self.field = self.board.field(self.size);
+        // the autopilot's cycle needs an even side
+        if self.mode == Mode::Autopilot && self.field.0 % 2 == 1 && self.field.1 % 2 == 1 {
+            self.field.0 -= 1;
+        }
+    }
+    // a random cell clear of both snakes, the apple and the item
+    fn free_cell(&self) -> Option<(i32, i32)> {
+        let (w, h) = self.field;
+        let free: Vec<(i32, i32)> = (0..w)
+            .flat_map(|x| (0..h).map(move |y| (x, y)))
+            .filter(|cell| {
+                !self.snake.body.contains(cell)
+                    && !self.rival.body.contains(cell)
+                    && *cell != (self.apple.0, self.apple.1)
+                    && self.item.as_ref().is_none_or(|item| item.cell != *cell)
+            })
+            .collect();
+        match free.is_empty() {
+            true => None,
+            false => Some(free[rand::rng().random_range(0..free.len())]),
+        }
+    }
+    // no cell left for the apple ends the game, in versus the round by score
+    fn set_apple(&mut self) {
+        let Some((x, y)) = self.free_cell() else {
+            match self.mode {
+                Mode::Versus => self.round(match self.score.get().cmp(&self.rival_score.get()) {
+                    std::cmp::Ordering::Greater => Some(0),
+                    std::cmp::Ordering::Less => Some(1),
+                    std::cmp::Ordering::Equal => None,
+                }),
+                _ => self.over("filled the whole board"),
+            }
+            return;
+        };
+        self.apple = Apple(x, y);
+        self.apple_life = APPLE_LIFE;
+    }
+    fn set_item(&mut self, dt: f32) {
+        self.slow = (self.slow - dt).max(0.0);
+        self.snake.ghost = (self.snake.ghost - dt).max(0.0);
+        self.rival.ghost = (self.rival.ghost - dt).max(0.0);
+        match &mut self.item {
+            Some(item) => {
+                item.life -= dt;
+                if item.life <= 0.0 {
+                    self.item = None;
+                }
+            }
+            None => {
+                self.item_timer += dt;
+                if self.item_timer >= ITEM_SPAWN {
+                    self.item_timer = 0.0;
+                    self.item = self.free_cell().map(Item::new);
+                }
+            }
+        }
+    }
+    fn pickup(&mut self) {
+        for (snake, score) in [
+            (&mut self.snake, &mut self.score),
+            (&mut self.rival, &mut self.rival_score),
+        ] {
+            let Some(item) = self
+                .item
+                .take_if(|item| snake.body.first() == Some(&item.cell))
+            else {
+                continue;
+            };
+            match item.power {
+                Power::Golden => {
+                    score.add(5);
+                    score.update();
+                }
+                Power::Slow => self.slow = item.power.duration(),
+                Power::Shrink => snake.shrink(3),
+                Power::Ghost => snake.ghost = item.power.duration(),
+            }
+        }
+    }
+    fn setup(&mut self) {
+        self.set_field();
+        match self.mode {
+            Mode::Versus => {
+                let (w, h) = self.field;
+                self.snake.spawn((w / 4, h / 3), (1, 0));
+                self.rival.spawn((w / 4 * 3, h / 3 * 2), (-1, 0));
+            }
+            _ => {
+                self.snake.set(self.field);
+                self.snake.phantom = self.mode == Mode::Zen;
+                self.rival = Snake::default();
+            }
+        }
+        self.score = Score::default();
+        self.rival_score = Score::default();
+        self.elapsed = 0.0;
+        self.apples = 0;
+        self.hunger = 0.0;
+        self.item = None;
+        self.item_timer = 0.0;
+        self.slow = 0.0;
+        self.set_apple();
+        self.bot = Autopilot::new(self.field);
+        self.trail = [None; 2];
+        self.timer = 0.0;
+        self.scene = Scene::Playing;
+    }
+    fn interval(&self) -> f32 {
+        let level = match self.mode {
+            Mode::Zen => 0,
+            _ => self.score.level().max(self.rival_score.level()),
+        };
+        let slow = if self.slow > 0.0 { 2.0 } else { 1.0 };
+        slow / (3.0 + level as f32)
+    }
+    fn decided(&self) -> bool {
+        self.wins.contains(&ROUNDS)
+    }
+    // what a run is ranked by, survival counts whole seconds alive
+    fn result(&self) -> i32 {
+        match self.mode {
+            Mode::Survival => self.elapsed as i32,
+            _ => self.score.get(),
+        }
+    }
+    fn over(&mut self, cause: &'static str) {
+        self.cause = cause;
+        let result = self.result();
+        let best = self.best.entry(self.record()).or_default();
+        *best = (*best).max(result);
+        self.scene = Scene::GameOver;
+    }
+    fn record(&self) -> String {
+        format!("{} {}", self.mode.label(), self.board.label())
+    }
+    fn round(&mut self, victor: Option<usize>) {
+        self.victor = victor;
+        if let Some(victor) = self.victor {
+            self.wins[victor] += 1;
+        }
+        self.scene = Scene::RoundOver;
+    }
+    fn rules(&mut self, dt: f32) {
+        match self.mode {
+            Mode::TimeAttack(limit) if self.elapsed >= limit as f32 => self.over("time is up"),
+            Mode::Survival => {
+                self.hunger += dt;
+                self.apple_life -= dt;
+                if self.hunger >= STARVE {
+                    self.over("starved");
+                } else if self.apple_life <= 0.0 {
+                    self.set_apple();
+                }
+            }
+            _ => {}
+        }
+    }
+    fn solo(&mut self) {
+        if let Mode::Autopilot = self.mode {
+            let (x, y) = self.bot.steer(&self.snake, &self.apple);
+            self.snake.direction(x, y);
+        }
+        match self.snake.update(self.field) {
+            Some(head) if head == (self.apple.0, self.apple.1) => {
+                self.trail[0] = None;
+                self.score.inc();
+                self.apples += 1;
+                self.hunger = 0.0;
+                self.set_apple();
+                if let Scene::GameOver = self.scene {
+                    return;
+                }
+            }
+            Some(_) => self.trail[0] = self.snake.body.pop(),
+            None => {
+                self.over("ran into its own body");
+                return;
+            }
+        }
+        self.pickup();
+        self.score.update();
+    }
+    fn versus(&mut self) {
+        let moved = [self.snake.update(self.field), self.rival.update(self.field)];
+        // head-to-body and head-to-head both count as running into the other snake
+        let crashed = [
+            moved[0].is_none_or(|head| self.snake.ghost <= 0.0 && self.rival.body.contains(&head)),
+            moved[1].is_none_or(|head| self.rival.ghost <= 0.0 && self.snake.body.contains(&head)),
+        ];
+        if crashed.contains(&true) {
+            self.round(match crashed {
+                [false, true] => Some(0),
+                [true, false] => Some(1),
+                _ => None,
+            });
+            return;
+        }
+        let mut eaten = false;
+        for ((snake, score), trail) in [
+            (&mut self.snake, &mut self.score),
+            (&mut self.rival, &mut self.rival_score),
+        ]
+        .into_iter()
+        .zip(&mut self.trail)
+        {
+            *trail = if snake.head() == (self.apple.0, self.apple.1) {
+                score.inc();
+                score.update();
+                eaten = true;
+                None
+            } else {
+                snake.body.pop()
+            };
+        }
+        if eaten {
+            self.set_apple();
+        }
+        self.pickup();
+    }
+    // square cells centered in the window, letterboxed on the longer side
+    fn layout(&self, window: &Window) -> (i32, i32, i32) {
+        let cell = (window.w() / self.field.0).min(window.h() / self.field.1);
+        (
+            (window.w() - cell * self.field.0) / 2,
+            (window.h() - cell * self.field.1) / 2,
+            cell,
+        )
     }
-    fn set_apple(&mut self, field: (i32, i32)) {
-        let Apple(x, y) = Apple::new(field);
-        match self.snake.body.contains(&(x, y)) {
-            true => self.set_apple(self.field),
-            false => self.apple = Apple(x, y),
+    fn draw_board(&self, window: &Window) {
+        let (left, top, cell) = self.layout(window);
+        // GRID
+        draw::set_draw_color(Color::Foreground);
+        draw::draw_rect(
+            left - 1,
+            top - 1,
+            cell * self.field.0 + 2,
+            cell * self.field.1 + 2,
+        );
+        for x in 0..self.field.0 {
+            for y in 0..self.field.1 {
+                if (x + y) % 2 == 0 {
+                    draw::draw_rect_fill(
+                        left + x * cell,
+                        top + y * cell,
+                        cell,
+                        cell,
+                        Color::Background2,
+                    );
+                }
+            }
+        }
+        let cell = (left, top, cell);
+        // SNAKES
+        let motion = match self.smooth {
+            true => Some((self.timer / self.interval()).min(1.0)),
+            false => None,
         };
+        draw_snake(
+            &self.snake,
+            cell,
+            Color::Cyan,
+            Color::Green,
+            motion.map(|t| (self.trail[0], t)),
+        );
+        // HUD
+        let hud = match self.mode {
+            Mode::Versus => {
+                draw_snake(
+                    &self.rival,
+                    cell,
+                    Color::Magenta,
+                    Color::Yellow,
+                    motion.map(|t| (self.trail[1], t)),
+                );
+                format!(
+                    "{}: {}   {}: {}",
+                    PLAYERS[0],
+                    self.score.get(),
+                    PLAYERS[1],
+                    self.rival_score.get()
+                )
+            }
+            _ => {
+                let (clock, time) = match self.mode {
+                    Mode::TimeAttack(limit) => ("Left", (limit as f32 - self.elapsed).max(0.0)),
+                    _ => ("Time", self.elapsed),
+                };
+                let mut hud = format!(
+                    "Score: {}   Length: {}   Level: {}   {clock}: {}:{:02}",
+                    self.score.get(),
+                    self.snake.body.len(),
+                    self.score.level(),
+                    time as u32 / 60,
+                    time as u32 % 60,
+                );
+                if let Mode::Survival = self.mode {
+                    hud += &format!("   Food: {:.1}s", STARVE - self.hunger);
+                }
+                hud
+            }
+        };
+        window.draw_text(&hud, PAD, PAD, Color::Foreground, Align::Left, 22);
+        let (left, top, cell) = cell;
+        // APPLE, in survival it runs down like the items do
+        let life = match self.mode {
+            Mode::Survival => self.apple_life / APPLE_LIFE,
+            _ => 1.0,
+        };
+        draw::set_draw_color(Color::Red);
+        draw::draw_pie(
+            left + self.apple.0 * cell,
+            top + self.apple.1 * cell,
+            cell,
+            cell,
+            90.0,
+            90.0 + 360.0 * life as f64,
+        );
+        // ITEM, the pie shrinks with the time it stays on the board
+        if let Some(item) = &self.item {
+            draw::set_draw_color(match item.power {
+                Power::Golden => Color::Yellow,
+                Power::Slow => Color::Blue,
+                Power::Shrink => Color::Magenta,
+                Power::Ghost => Color::Inactive,
+            });
+            draw::draw_pie(
+                left + item.cell.0 * cell,
+                top + item.cell.1 * cell,
+                cell,
+                cell,
+                90.0,
+                90.0 + 360.0 * item.life as f64 / ITEM_LIFE as f64,
+            );
+        }
+        // EFFECTS
+        let effects: Vec<String> = [
+            (Power::Slow, self.slow),
+            (Power::Ghost, self.snake.ghost.max(self.rival.ghost)),
+        ]
+        .iter()
+        .filter(|(_, time)| *time > 0.0)
+        .map(|(power, time)| format!("{} {time:.1}s", power.label()))
+        .collect();
+        window.draw_text(
+            &effects.join("   "),
+            PAD,
+            PAD + HEIGHT,
+            Color::Foreground,
+            Align::Left,
+            22,
+        );
     }
 }
 
 impl Console for Model {
-    fn load(&mut self, _path: &str) {}
-    fn exit(&self, _path: &str) {}
+    fn load(&mut self, path: &str) {
+        self.path = path.to_string();
+        for (key, value) in load_lines(path) {
+            if key == "best"
+                && let Some((record, score)) = value.rsplit_once(' ')
+                && let Ok(score) = score.parse()
+            {
+                self.best.insert(record.to_string(), score);
+            }
+        }
+    }
+    fn exit(&self, path: &str) {
+        let save: Vec<(&str, String)> = self
+            .best
+            .iter()
+            .map(|(record, score)| ("best", format!("{record} {score}")))
+            .collect();
+        save_lines(path, &save);
+    }
     fn handle(&mut self, window: &mut Window, event: Event) -> bool {
         match event {
             Event::Focus => true,
             Event::Resize => {
-                self.snake.set((window.w(), window.h()));
-                self.play = false;
+                self.size = (window.w(), window.h());
+                self.scene = Scene::Welcome;
                 true
             }
             Event::KeyDown => {
@@ -40,86 +426,228 @@ impl Console for Model {
                 const RIGHT: Key = Key::from_char('d');
                 const UP: Key = Key::from_char('w');
                 const DOWN: Key = Key::from_char('s');
-                match event_key() {
-                    Key::Escape => match self.play {
-                        true => self.play = false,
-                        false => crate::Model::connect(window),
-                    },
-                    Key::Enter => {
-                        if !self.play {
-                            self.set_field();
-                            self.snake.set(self.field);
-                            self.score = Score::default();
-                            self.set_apple(self.field);
-                            self.play = true;
+                const SPACE: Key = Key::from_char(' ');
+                const MOTION: Key = Key::from_char('m');
+                let Scene::Playing = self.scene else {
+                    match event_key() {
+                        Key::Escape => match self.scene {
+                            Scene::Welcome => {
+                                self.exit(&self.path);
+                                crate::Model::connect(window);
+                            }
+                            _ => self.scene = Scene::Welcome,
+                        },
+                        Key::Enter => match self.scene {
+                            Scene::RoundOver if self.decided() => self.scene = Scene::Welcome,
+                            Scene::RoundOver => self.setup(),
+                            Scene::GameOver => self.scene = Scene::Welcome,
+                            _ => {
+                                self.wins = [0; 2];
+                                self.setup();
+                            }
+                        },
+                        Key::Tab => {
+                            if let Scene::Welcome = self.scene {
+                                self.mode = self.mode.switch();
+                                window.redraw();
+                            }
+                        }
+                        SPACE => {
+                            if let Scene::Welcome = self.scene {
+                                self.board = self.board.switch();
+                                window.redraw();
+                            }
+                        }
+                        MOTION => {
+                            if let Scene::Welcome = self.scene {
+                                self.smooth = !self.smooth;
+                                window.redraw();
+                            }
                         }
+                        _ => return false,
+                    };
+                    return true;
+                };
+                let (player, (x, y)) = match event_key() {
+                    Key::Escape => {
+                        match self.mode {
+                            Mode::Zen => self.over("left the garden"),
+                            _ => self.scene = Scene::Welcome,
+                        }
+                        return true;
                     }
-                    Key::Up | UP => self.snake.direction(0, -1),
-                    Key::Down | DOWN => self.snake.direction(0, 1),
-                    Key::Left | LEFT => self.snake.direction(-1, 0),
-                    Key::Right | RIGHT => self.snake.direction(1, 0),
+                    _ if self.mode == Mode::Autopilot => return false,
+                    UP => (0, (0, -1)),
+                    DOWN => (0, (0, 1)),
+                    LEFT => (0, (-1, 0)),
+                    RIGHT => (0, (1, 0)),
+                    Key::Up => (1, (0, -1)),
+                    Key::Down => (1, (0, 1)),
+                    Key::Left => (1, (-1, 0)),
+                    Key::Right => (1, (1, 0)),
                     _ => return false,
                 };
+                match (player, &self.mode) {
+                    (1, Mode::Versus) => self.rival.direction(x, y),
+                    _ => self.snake.direction(x, y),
+                }
                 true
             }
             _ => false,
         }
     }
     fn update(&mut self, dt: f32) {
-        if !self.play {
+        let Scene::Playing = self.scene else {
+            return;
+        };
+        self.set_item(dt);
+        self.elapsed += dt;
+        self.rules(dt);
+        if let Scene::GameOver = self.scene {
             return;
         }
         self.timer += dt;
-        if self.timer < 1.0 / (3.0 + self.score.level() as f32) {
+        if self.timer < self.interval() {
             return;
         }
         self.timer = 0.0;
-        self.play = if let Some((x, y)) = self.snake.update() {
-            let Apple(k, l) = self.apple;
-            if (x, y) == (k, l) {
-                self.set_apple(self.field);
-                self.score.inc();
-            } else {
-                self.snake.body.pop();
-            }
-            self.score.update();
-            true
-        } else {
-            false
-        };
+        match self.mode {
+            Mode::Versus => self.versus(),
+            _ => self.solo(),
+        }
     }
     fn draw(&self, window: &mut Window) {
         // BACKGROUND
         draw::draw_rect_fill(0, 0, window.width(), window.height(), Color::Background);
-        if self.play {
-            // GRID
-            let cell = window.width() / COLS;
-            for x in 0..COLS {
-                for y in 0..ROWS {
-                    if (x + y) % 2 == 0 {
-                        draw::draw_rect_fill(x * cell, y * cell, cell, cell, Color::Background2);
-                    }
-                }
-            }
-            // SNAKE BODY
-            for &seg in &self.snake.body {
-                draw::draw_rect_fill(seg.0 * cell, seg.1 * cell, cell, cell, Color::Cyan);
+        match self.scene {
+            Scene::Playing => self.draw_board(window),
+            Scene::RoundOver => {
+                self.draw_board(window);
+                let title = match (self.victor, self.decided()) {
+                    (Some(victor), true) => format!("{} wins the match", PLAYERS[victor]),
+                    (Some(victor), false) => format!("{} wins the round", PLAYERS[victor]),
+                    (None, _) => String::from("Draw"),
+                };
+                let next = match self.decided() {
+                    true => "menu",
+                    false => "next round",
+                };
+                window.draw_overlay(
+                    &title,
+                    &format!(
+                        "Rounds {} : {}   <ENTER>: {next}",
+                        self.wins[0], self.wins[1]
+                    ),
+                    Color::Foreground,
+                );
             }
-            // SNAKE HEAD
-            draw::draw_rect_fill(
-                self.snake.body[0].0 * cell,
-                self.snake.body[0].1 * cell,
-                cell,
-                cell,
-                Color::Green,
-            );
-            // APPLE
-            draw::draw_circle_fill(self.apple.0 * cell, self.apple.1 * cell, cell, Color::Red);
-        } else {
-            window.draw_welcome(
+            Scene::GameOver => window.draw_welcome(
+                "Game Over",
+                &[
+                    &["LENGTH", &self.snake.body.len().to_string()],
+                    &["APPLES", &self.apples.to_string()],
+                    &["MODE", &self.mode.label()],
+                    &["SCORE", &self.result().to_string()],
+                    &[
+                        "BEST",
+                        &self.best.get(&self.record()).unwrap_or(&0).to_string(),
+                    ],
+                    &["CAUSE", self.cause],
+                    &["PRESS ENTER", "for menu"],
+                ],
+            ),
+            Scene::Welcome => window.draw_welcome(
                 "Snake",
-                &[&["PRESS ENTER", "for play"], &["PRESS ESC", "for exit"]],
-            );
+                &[
+                    &["PRESS ENTER", "for play"],
+                    &["PRESS ESC", "for exit"],
+                    &["PRESS TAB", &format!("for mode: {}", self.mode.label())],
+                    &["PRESS SPACE", &format!("for board: {}", self.board.label())],
+                    &[
+                        "PRESS M",
+                        match self.smooth {
+                            true => "for motion: Smooth",
+                            false => "for motion: Steps",
+                        },
+                    ],
+                ],
+            ),
         }
     }
 }
+
+// `motion` holds the cell the tail just left and how far the current tick has run,
+// the head and tail then slide between cells instead of jumping
+fn draw_snake(
+    snake: &Snake,
+    (left, top, cell): (i32, i32, i32),
+    body: Color,
+    head: Color,
+    motion: Option<(Option<(i32, i32)>, f32)>,
+) {
+    let body = match snake.ghost > 0.0 {
+        true => Color::Inactive,
+        false => body,
+    };
+    let at = |(x, y): (i32, i32)| (left + x * cell, top + y * cell);
+    let Some((trail, t)) = motion else {
+        for &seg in &snake.body {
+            let (x, y) = at(seg);
+            draw::draw_rect_fill(x, y, cell, cell, body);
+        }
+        let (x, y) = at(snake.head());
+        draw::draw_rect_fill(x, y, cell, cell, head);
+        return;
+    };
+    let slide = |from: (i32, i32), to: (i32, i32)| {
+        let (x, y) = at(to);
+        match step(from, to) {
+            Some((dx, dy)) => (
+                x - (dx as f32 * cell as f32 * (1.0 - t)) as i32,
+                y - (dy as f32 * cell as f32 * (1.0 - t)) as i32,
+            ),
+            None => (x, y),
+        }
+    };
+    let last = snake.body.len() - 1;
+    if let Some(trail) = trail {
+        let (x, y) = slide(trail, snake.body[last]);
+        draw::draw_rect_fill(x, y, cell, cell, body);
+    }
+    for (i, &seg) in snake.body.iter().enumerate().skip(1) {
+        let (x, y) = at(seg);
+        let prev = snake.body[i - 1];
+        let next = match i == last {
+            true => trail,
+            false => Some(snake.body[i + 1]),
+        };
+        match next.and_then(|next| Some((step(seg, prev)?, step(seg, next)?))) {
+            // a turn: round the outer corner, keep both joints flush
+            Some((a, b)) if a.0 != -b.0 || a.1 != -b.1 => {
+                draw::set_draw_color(body);
+                draw::draw_rounded_rectf(x, y, cell, cell, cell / 2);
+                for (dx, dy) in [a, b] {
+                    draw::draw_rect_fill(
+                        x + (dx.max(0) * cell) / 2,
+                        y + (dy.max(0) * cell) / 2,
+                        cell - (dx.abs() * cell) / 2,
+                        cell - (dy.abs() * cell) / 2,
+                        body,
+                    );
+                }
+            }
+            _ => draw::draw_rect_fill(x, y, cell, cell, body),
+        }
+    }
+    let (x, y) = match (snake.body.get(1).copied(), trail) {
+        (Some(from), _) | (None, Some(from)) => slide(from, snake.head()),
+        (None, None) => at(snake.head()),
+    };
+    draw::draw_rect_fill(x, y, cell, cell, head);
+}
+
+// unit offset from one cell to a neighbouring one, `None` across the wrapping edge
+fn step(from: (i32, i32), to: (i32, i32)) -> Option<(i32, i32)> {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    (dx.abs() + dy.abs() == 1).then_some((dx, dy))
+}
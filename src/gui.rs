@@ -17,8 +17,6 @@ pub use {
 
 pub const SCREEN_WIDTH: i32 = 960;
 pub const SCREEN_HEIGHT: i32 = 540;
-pub const COLS: i32 = SCREEN_WIDTH / 20;
-pub const ROWS: i32 = SCREEN_HEIGHT / 20;
 
 pub const PAD: i32 = 10;
 pub const HEIGHT: i32 = 3 * PAD;
@@ -102,6 +100,27 @@ where
     }
 }
 
+// Save files hold one `key value` line per entry, a missing file is empty.
+pub fn load_lines(path: &str) -> Vec<(String, String)> {
+    std::fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+pub fn save_lines(path: &str, lines: &[(&str, String)]) {
+    if let Some(dir) = std::path::Path::new(path).parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    let save: String = lines
+        .iter()
+        .map(|(key, value)| format!("{key} {value}\n"))
+        .collect();
+    let _ = std::fs::write(path, save);
+}
+
 pub fn set_theme(theme: usize) {
     const COLOR: [[u32; 5]; 4] = [
         [
@@ -11,6 +11,7 @@ pub enum Game {
     Snake,
     Bird,
     Pong,
+    Breakout,
     Runner,
 }
 
@@ -20,7 +21,8 @@ impl Game {
             Self::Tetris => Self::Snake,
             Self::Snake => Self::Bird,
             Self::Bird => Self::Pong,
-            Self::Pong => Self::Runner,
+            Self::Pong => Self::Breakout,
+            Self::Breakout => Self::Runner,
             Self::Runner => Self::Tetris,
         }
     }
@@ -30,6 +32,7 @@ impl Game {
             Self::Snake => "Snake",
             Self::Bird => "Bird",
             Self::Pong => "Pong",
+            Self::Breakout => "Breakout",
             Self::Runner => "Runner",
         }
     }
@@ -39,6 +42,7 @@ impl Game {
             Self::Snake => games::snake::Model::connect(window),
             Self::Bird => games::bird::Model::connect(window),
             Self::Pong => games::pong::Model::connect(window),
+            Self::Breakout => games::breakout::Model::connect(window),
             Self::Runner => games::runner::Model::connect(window),
         }
     }
@@ -85,6 +89,23 @@ impl Console for Model {
 }
 
 fn main() -> Result<(), FltkError> {
+    // `--train-bird [GENERATIONS]` evolves a Bird pilot headless, no window
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(at) = args.iter().position(|arg| arg == "--train-bird") {
+        let generations = args
+            .get(at + 1)
+            .and_then(|arg| arg.parse().ok())
+            .unwrap_or(100);
+        models::bird::train(
+            generations,
+            (SCREEN_WIDTH as f32, SCREEN_HEIGHT as f32),
+            &format!("{}/.config/Bird.brain", std::env::var("HOME").unwrap()),
+            |generation, score, best| {
+                println!("generation {generation:>4}   score {score:>4}   best {best:>4}")
+            },
+        );
+        return Ok(());
+    }
     Model::run(Settings {
         fullscreen: true,
         size: Some((SCREEN_WIDTH, SCREEN_HEIGHT)),
@@ -0,0 +1,659 @@
+use crate::*;
+use rand::{RngExt, SeedableRng, rngs::StdRng};
+
+// Tuning, distances in pixels and times in seconds.
+pub const BIRD: f32 = 60.0;
+pub const GRAVITY: f32 = 2500.0;
+pub const FLAP: f32 = -400.0;
+pub const SCROLL: f32 = 200.0;
+pub const PIPE_WIDTH: f32 = BIRD * 2.0;
+pub const PIPE_GAP: f32 = BIRD * 5.0;
+// open air between one pipe and the next
+pub const PIPE_SPACING: f32 = 180.0;
+// score at which pipes stop getting harder
+pub const RAMP: f32 = 40.0;
+// how fast the bird can climb by flapping steadily and how fast it can dive,
+// consecutive gaps never move further apart than this allows
+pub const CLIMB: f32 = 180.0;
+pub const DIVE: f32 = 400.0;
+// longest step integrated at once, a hiccup in redraws must not teleport the bird
+pub const MAX_STEP: f32 = 0.05;
+// horizontal position of the bird, pipes scroll past it
+pub const PERCH: f32 = 100.0;
+// neuroevolution: network shape, population size and the fixed training step
+pub const INPUTS: usize = 5;
+pub const HIDDEN: usize = 6;
+pub const GENES: usize = (INPUTS + 1) * HIDDEN + HIDDEN + 1;
+pub const POPULATION: usize = 50;
+pub const ELITE: usize = 4;
+pub const MUTATION: f32 = 0.1;
+pub const TRAIN_STEP: f32 = 0.02;
+// a trained run stops here so a perfect flock still moves to the next generation
+pub const TRAIN_SCORE: i32 = 200;
+
+#[derive(Default)]
+pub enum Scene {
+    #[default]
+    Welcome = 0,
+    Ready,
+    Playing,
+    Paused,
+    GameOver,
+}
+
+#[derive(Default)]
+pub enum Difficulty {
+    #[default]
+    Normal = 0,
+    Hard,
+    Easy,
+}
+
+impl Difficulty {
+    pub fn switch(&self) -> Self {
+        match self {
+            Self::Easy => Self::Normal,
+            Self::Normal => Self::Hard,
+            Self::Hard => Self::Easy,
+        }
+    }
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Easy => "Easy",
+            Self::Normal => "Normal",
+            Self::Hard => "Hard",
+        }
+    }
+    pub fn from_label(label: &str) -> Option<Self> {
+        match label {
+            "Easy" => Some(Self::Easy),
+            "Normal" => Some(Self::Normal),
+            "Hard" => Some(Self::Hard),
+            _ => None,
+        }
+    }
+    pub fn gap_scale(&self) -> f32 {
+        match self {
+            Self::Easy => 1.2,
+            Self::Normal => 1.0,
+            Self::Hard => 0.85,
+        }
+    }
+    pub fn speed_mul(&self) -> f32 {
+        match self {
+            Self::Easy => 0.85,
+            Self::Normal => 1.0,
+            Self::Hard => 1.15,
+        }
+    }
+    // gap, scroll speed and spacing between pipes for the current score
+    pub fn pace(&self, score: i32) -> (f32, f32, f32) {
+        let ramp = (score as f32 / RAMP).min(1.0);
+        (
+            PIPE_GAP * self.gap_scale() * (1.0 - 0.25 * ramp),
+            SCROLL * self.speed_mul() * (1.0 + 0.5 * ramp),
+            PIPE_SPACING * self.gap_scale() * (1.0 - 0.3 * ramp),
+        )
+    }
+}
+
+// Who flies the bird: the player, the saved trained brain, or a live
+// generation evolving on screen.
+#[derive(Default)]
+pub enum Control {
+    #[default]
+    Player = 0,
+    Trained,
+    Evolve,
+}
+
+impl Control {
+    pub fn switch(&self) -> Self {
+        match self {
+            Self::Player => Self::Trained,
+            Self::Trained => Self::Evolve,
+            Self::Evolve => Self::Player,
+        }
+    }
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Player => "Player",
+            Self::Trained => "Trained",
+            Self::Evolve => "Evolve",
+        }
+    }
+}
+
+// Pipe layout generator, the same seed always builds the same course.
+pub struct Course {
+    pub seed: u64,
+    rng: StdRng,
+}
+
+impl Default for Course {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl Course {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+    // the next obstacle, special kinds get more likely as the score rises
+    pub fn pipes(
+        &mut self,
+        difficulty: &Difficulty,
+        score: i32,
+        prev: Option<&Pipe>,
+        field: (f32, f32),
+    ) -> Vec<Pipe> {
+        let (gap, scroll, spacing) = difficulty.pace(score);
+        let x = field.0;
+        let time = (spacing + PIPE_WIDTH) / scroll;
+        let reach = |gap: f32, margin: f32| {
+            let (lo, hi) = (gap / 2.0 + margin, field.1 - gap / 2.0 - margin);
+            match prev {
+                Some(prev) => (
+                    lo.max(prev.center + prev.swing - CLIMB * time + margin),
+                    hi.min(prev.center - prev.swing + DIVE * time - margin),
+                ),
+                None => (lo, hi),
+            }
+        };
+        let variety = (score as f32 / RAMP).min(1.0) * 0.6;
+        let kind = match self.rng.random_bool(variety as f64) {
+            true => self.rng.random_range(1..4),
+            false => 0,
+        };
+        match kind {
+            // gap swinging up and down, static when there is no room to swing
+            1 => {
+                let swing = match reach(gap, gap * 0.4) {
+                    (lo, hi) if lo < hi => gap * 0.4,
+                    _ => 0.0,
+                };
+                let (lo, hi) = reach(gap, swing);
+                vec![Pipe::new(
+                    x,
+                    between(&mut self.rng, lo, hi),
+                    gap,
+                    PIPE_WIDTH,
+                    swing,
+                )]
+            }
+            // two pipes close together with the second gap shifted
+            2 => {
+                let (lo, hi) = reach(gap, 0.0);
+                let first = between(&mut self.rng, lo, hi);
+                let offset = (gap * 0.5).min(CLIMB * PIPE_WIDTH * 1.5 / scroll);
+                let second = match self.rng.random_bool(0.5) {
+                    true => first - offset,
+                    false => first + offset,
+                }
+                .clamp(gap / 2.0, field.1 - gap / 2.0);
+                vec![
+                    Pipe::new(x, first, gap, PIPE_WIDTH, 0.0),
+                    Pipe::new(x + PIPE_WIDTH * 2.5, second, gap, PIPE_WIDTH, 0.0),
+                ]
+            }
+            // long and narrow
+            3 => {
+                let (lo, hi) = reach(gap * 0.8, 0.0);
+                vec![Pipe::new(
+                    x,
+                    between(&mut self.rng, lo, hi),
+                    gap * 0.8,
+                    PIPE_WIDTH * 3.0,
+                    0.0,
+                )]
+            }
+            _ => {
+                let (lo, hi) = reach(gap, 0.0);
+                vec![Pipe::new(
+                    x,
+                    between(&mut self.rng, lo, hi),
+                    gap,
+                    PIPE_WIDTH,
+                    0.0,
+                )]
+            }
+        }
+    }
+}
+
+// random point in `lo..hi`, the middle when the range is empty
+fn between(rng: &mut StdRng, lo: f32, hi: f32) -> f32 {
+    match lo < hi {
+        true => rng.random_range(lo..hi),
+        false => (lo + hi) / 2.0,
+    }
+}
+
+#[derive(Default)]
+pub struct Bird {
+    pub position: (f32, f32),
+    pub velocity: f32,
+}
+
+impl Bird {
+    pub fn new(height: f32) -> Self {
+        Self {
+            position: (PERCH, height / 2.0),
+            velocity: 0.0,
+        }
+    }
+    pub fn flap(&mut self) {
+        self.velocity = FLAP;
+    }
+    pub fn update(&mut self, dt: f32) {
+        self.velocity += GRAVITY * dt;
+        self.position.1 += self.velocity * dt;
+    }
+    pub fn collides(&self, rec: (f32, f32, f32, f32)) -> bool {
+        // Find the closest point on the rectangle to the center of the circle
+        let radius = BIRD / 2.0;
+        let center = (self.position.0 + radius, self.position.1 + radius);
+        let closest_x = center.0.max(rec.0).min(rec.0 + rec.2);
+        let closest_y = center.1.max(rec.1).min(rec.1 + rec.3);
+
+        // Calculate the distance between the closest point and the circle's center
+        let distance_x = center.0 - closest_x;
+        let distance_y = center.1 - closest_y;
+        let distance_squared = (distance_x * distance_x) + (distance_y * distance_y);
+
+        // If the distance squared is less than the circle's radius squared, there is a collision
+        distance_squared < (radius * radius)
+    }
+}
+
+pub struct Pipe {
+    pub x: f32,
+    pub width: f32,
+    pub gap: f32,
+    pub center: f32,
+    // vertical amplitude of a moving gap, zero for a static one
+    pub swing: f32,
+    pub time: f32,
+    pub scored: bool,
+}
+
+impl Pipe {
+    pub fn new(x: f32, center: f32, gap: f32, width: f32, swing: f32) -> Self {
+        Self {
+            x,
+            width,
+            gap,
+            center,
+            swing,
+            time: 0.0,
+            scored: false,
+        }
+    }
+    pub fn update(&mut self, dt: f32, scroll: f32) {
+        self.x -= scroll * dt;
+        self.time += dt;
+    }
+    pub fn gap_center_y(&self) -> f32 {
+        self.center + self.swing * (self.time * 2.0).sin()
+    }
+    pub fn top_rect(&self) -> (f32, f32, f32, f32) {
+        let top_height = self.gap_center_y() - (self.gap / 2.0);
+        (self.x, 0.0, self.width, top_height)
+    }
+    pub fn bottom_rect(&self, height: f32) -> (f32, f32, f32, f32) {
+        let bottom_y = self.gap_center_y() + (self.gap / 2.0);
+        let bottom_height = height - bottom_y;
+        (self.x, bottom_y, self.width, bottom_height)
+    }
+}
+
+// Recorded flight of a best run: bird height sampled over scrolled distance.
+#[derive(Default)]
+pub struct Ghost {
+    pub seed: u64,
+    pub difficulty: String,
+    pub height: f32,
+    pub path: Vec<(f32, f32)>,
+}
+
+impl Ghost {
+    pub fn new(seed: u64, difficulty: &Difficulty, height: f32) -> Self {
+        Self {
+            seed,
+            difficulty: difficulty.label().to_string(),
+            height,
+            path: Vec::new(),
+        }
+    }
+    pub fn record(&mut self, distance: f32, y: f32) {
+        if self
+            .path
+            .last()
+            .is_none_or(|(last, _)| distance - last >= 8.0)
+        {
+            self.path.push((distance, y));
+        }
+    }
+    pub fn fits(&self, difficulty: &Difficulty, height: f32) -> bool {
+        !self.path.is_empty() && self.difficulty == difficulty.label() && self.height == height
+    }
+    // bird height at `distance`, `None` once the recorded run has crashed
+    pub fn at(&self, distance: f32) -> Option<f32> {
+        let next = self.path.iter().position(|(d, _)| *d >= distance)?;
+        let (d1, y1) = self.path[next];
+        match next.checked_sub(1).map(|prev| self.path[prev]) {
+            Some((d0, y0)) if d1 > d0 => Some(y0 + (y1 - y0) * (distance - d0) / (d1 - d0)),
+            _ => Some(y1),
+        }
+    }
+    pub fn to_line(&self) -> String {
+        let path: Vec<String> = self.path.iter().map(|(d, y)| format!("{d}:{y}")).collect();
+        format!(
+            "{} {} {} {}",
+            self.seed,
+            self.difficulty,
+            self.height,
+            path.join(",")
+        )
+    }
+    pub fn from_line(line: &str) -> Option<Self> {
+        let mut parts = line.split(' ');
+        Some(Self {
+            seed: parts.next()?.parse().ok()?,
+            difficulty: parts.next()?.to_string(),
+            height: parts.next()?.parse().ok()?,
+            path: parts
+                .next()?
+                .split(',')
+                .map(|point| {
+                    let (d, y) = point.split_once(':')?;
+                    Some((d.parse().ok()?, y.parse().ok()?))
+                })
+                .collect::<Option<_>>()?,
+        })
+    }
+}
+
+// Pipes scrolling past the bird, shared by every bird flying the same course.
+#[derive(Default)]
+pub struct World {
+    pub field: (f32, f32),
+    pub pipes: Vec<Pipe>,
+    pub course: Course,
+    pub score: i32,
+    pub distance: f32,
+}
+
+impl World {
+    pub fn new(seed: u64, field: (f32, f32)) -> Self {
+        Self {
+            field,
+            pipes: Vec::new(),
+            course: Course::new(seed),
+            score: 0,
+            distance: 0.0,
+        }
+    }
+    pub fn update(&mut self, difficulty: &Difficulty, dt: f32) {
+        let (_, scroll, spacing) = difficulty.pace(self.score);
+        self.distance += scroll * dt;
+
+        for pipe in &mut self.pipes {
+            pipe.update(dt, scroll);
+        }
+
+        let last = self.pipes.last();
+        if last.is_none_or(|pipe| pipe.x + pipe.width + spacing <= self.field.0) {
+            let pipes = self.course.pipes(difficulty, self.score, last, self.field);
+            self.pipes.extend(pipes);
+        }
+
+        self.pipes.retain(|pipe| pipe.x > -pipe.width);
+
+        for pipe in &mut self.pipes {
+            if !pipe.scored && PERCH > pipe.x + pipe.width {
+                self.score += 1;
+                pipe.scored = true;
+            }
+        }
+    }
+    pub fn hits(&self, bird: &Bird) -> bool {
+        bird.position.1 - BIRD < 0.0
+            || bird.position.1 + BIRD > self.field.1
+            || self.pipes.iter().any(|pipe| {
+                bird.collides(pipe.top_rect()) || bird.collides(pipe.bottom_rect(self.field.1))
+            })
+    }
+    // first pipe the bird has not yet cleared
+    pub fn ahead(&self) -> Option<&Pipe> {
+        self.pipes.iter().find(|pipe| pipe.x + pipe.width > PERCH)
+    }
+}
+
+// Weights of a tiny feed-forward network deciding when to flap:
+// INPUTS -> HIDDEN tanh -> one sigmoid output, every neuron with a bias.
+#[derive(Clone, Default)]
+pub struct Brain(pub Vec<f32>);
+
+impl Brain {
+    pub fn random(rng: &mut StdRng) -> Self {
+        Self((0..GENES).map(|_| rng.random_range(-1.0..1.0)).collect())
+    }
+    // false until a trained or evolved brain is loaded
+    pub fn ready(&self) -> bool {
+        self.0.len() == GENES
+    }
+    pub fn think(&self, bird: &Bird, world: &World) -> bool {
+        if !self.ready() {
+            return false;
+        }
+        let (width, height) = world.field;
+        let inputs = match world.ahead() {
+            Some(pipe) => [
+                bird.position.1 / height,
+                bird.velocity / 1000.0,
+                (pipe.x - PERCH) / width,
+                (pipe.gap_center_y() - bird.position.1 - BIRD / 2.0) / height,
+                pipe.gap / height,
+            ],
+            None => [
+                bird.position.1 / height,
+                bird.velocity / 1000.0,
+                1.0,
+                0.0,
+                1.0,
+            ],
+        };
+        let (hidden, output) = self.0.split_at(HIDDEN * (INPUTS + 1));
+        let mut sum = output[HIDDEN];
+        for (neuron, weights) in hidden.chunks(INPUTS + 1).enumerate() {
+            let input: f32 =
+                weights[INPUTS] + inputs.iter().zip(weights).map(|(i, w)| i * w).sum::<f32>();
+            sum += input.tanh() * output[neuron];
+        }
+        1.0 / (1.0 + (-sum).exp()) > 0.5
+    }
+    // uniform crossover of two parents followed by gaussian mutation
+    pub fn child(&self, other: &Self, rng: &mut StdRng) -> Self {
+        Self(
+            self.0
+                .iter()
+                .zip(&other.0)
+                .map(|(a, b)| {
+                    let gene = if rng.random_bool(0.5) { *a } else { *b };
+                    match rng.random_bool(MUTATION as f64) {
+                        true => gene + gaussian(rng) * 0.5,
+                        false => gene,
+                    }
+                })
+                .collect(),
+        )
+    }
+    pub fn to_line(&self) -> String {
+        let genes: Vec<String> = self.0.iter().map(|gene| gene.to_string()).collect();
+        genes.join(" ")
+    }
+    pub fn from_line(line: &str) -> Option<Self> {
+        let genes: Vec<f32> = line
+            .split_whitespace()
+            .map(|gene| gene.parse().ok())
+            .collect::<Option<_>>()?;
+        (genes.len() == GENES).then_some(Self(genes))
+    }
+    // saved brain and the score it reached, the file holds `score genes..`
+    pub fn load(path: &str) -> Option<(Self, i32)> {
+        let (score, genes) = load_lines(path).into_iter().next()?;
+        Some((Self::from_line(&genes)?, score.parse().ok()?))
+    }
+    pub fn save(&self, path: &str, score: i32) {
+        save_lines(path, &[(&score.to_string(), self.to_line())]);
+    }
+}
+
+// standard normal sample, Box-Muller
+fn gaussian(rng: &mut StdRng) -> f32 {
+    let (u, v): (f32, f32) = (rng.random_range(f32::EPSILON..1.0), rng.random());
+    (-2.0 * u.ln()).sqrt() * (std::f32::consts::TAU * v).cos()
+}
+
+// One member of a generation: a brain and the bird it flies.
+pub struct Pilot {
+    pub brain: Brain,
+    pub bird: Bird,
+    pub alive: bool,
+    pub fitness: f32,
+}
+
+// A generation of pilots flying the same course, evolved by a genetic algorithm.
+pub struct Flock {
+    pub generation: u32,
+    pub pilots: Vec<Pilot>,
+    // best brain seen so far and the score it reached, -1 before any
+    pub champion: Brain,
+    pub record: i32,
+    rng: StdRng,
+}
+
+impl Default for Flock {
+    fn default() -> Self {
+        Self::new(rand::rng().random())
+    }
+}
+
+impl Flock {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let brains = (0..POPULATION).map(|_| Brain::random(&mut rng)).collect();
+        Self {
+            generation: 1,
+            pilots: Self::hatch(brains),
+            champion: Brain::default(),
+            record: -1,
+            rng,
+        }
+    }
+    fn hatch(brains: Vec<Brain>) -> Vec<Pilot> {
+        brains
+            .into_iter()
+            .map(|brain| Pilot {
+                brain,
+                bird: Bird::default(),
+                alive: true,
+                fitness: 0.0,
+            })
+            .collect()
+    }
+    // a fresh course for the current generation, every bird back at the start
+    pub fn launch(&mut self, field: (f32, f32)) -> World {
+        for pilot in &mut self.pilots {
+            pilot.bird = Bird::new(field.1);
+            pilot.alive = true;
+            pilot.fitness = 0.0;
+        }
+        World::new(self.rng.random(), field)
+    }
+    pub fn update(&mut self, world: &World, dt: f32) {
+        for pilot in self.pilots.iter_mut().filter(|pilot| pilot.alive) {
+            if pilot.brain.think(&pilot.bird, world) {
+                pilot.bird.flap();
+            }
+            pilot.bird.update(dt);
+            if world.hits(&pilot.bird) {
+                pilot.alive = false;
+            } else {
+                // distance flown, plus a nudge towards the gap so early generations
+                // that all die at the first pipe can still be told apart
+                let aim = world.ahead().map_or(0.0, |pipe| {
+                    (pipe.gap_center_y() - pilot.bird.position.1 - BIRD / 2.0).abs()
+                });
+                pilot.fitness = world.distance - aim;
+            }
+        }
+    }
+    pub fn alive(&self) -> usize {
+        self.pilots.iter().filter(|pilot| pilot.alive).count()
+    }
+    // the next generation: elites carried over, the rest bred from tournaments,
+    // true when this generation's best beat the champion
+    pub fn evolve(&mut self, score: i32) -> bool {
+        self.pilots.sort_by(|a, b| b.fitness.total_cmp(&a.fitness));
+        let improved = score > self.record;
+        if improved {
+            self.record = score;
+            self.champion = self.pilots[0].brain.clone();
+        }
+        let mut brains: Vec<Brain> = self.pilots[..ELITE]
+            .iter()
+            .map(|pilot| pilot.brain.clone())
+            .collect();
+        while brains.len() < POPULATION {
+            let a = self.select();
+            let b = self.select();
+            let child = self.pilots[a]
+                .brain
+                .child(&self.pilots[b].brain, &mut self.rng);
+            brains.push(child);
+        }
+        self.pilots = Self::hatch(brains);
+        self.generation += 1;
+        improved
+    }
+    // fittest of three random pilots, the list is sorted best first
+    fn select(&mut self) -> usize {
+        (0..3)
+            .map(|_| self.rng.random_range(0..self.pilots.len()))
+            .min()
+            .unwrap_or_default()
+    }
+}
+
+// Headless training: evolves `generations` generations on the normal difficulty
+// and saves the best brain to `path` whenever it beats the one already there.
+// `report` gets the generation, its score and the best score so far.
+pub fn train(
+    generations: u32,
+    field: (f32, f32),
+    path: &str,
+    mut report: impl FnMut(u32, i32, i32),
+) {
+    let difficulty = Difficulty::Normal;
+    let mut flock = Flock::default();
+    if let Some((_, score)) = Brain::load(path) {
+        flock.record = score;
+    }
+    for _ in 0..generations {
+        let mut world = flock.launch(field);
+        while flock.alive() > 0 && world.score < TRAIN_SCORE {
+            world.update(&difficulty, TRAIN_STEP);
+            flock.update(&world, TRAIN_STEP);
+        }
+        report(flock.generation, world.score, world.score.max(flock.record));
+        if flock.evolve(world.score) {
+            flock.champion.save(path, flock.record);
+        }
+    }
+}
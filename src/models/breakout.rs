@@ -0,0 +1,176 @@
+use crate::*;
+use rand::RngExt;
+
+pub const LIVES: i32 = 3;
+// speed of falling power-ups and of laser bolts, pixels per second
+pub const DROP_SPEED: f32 = 200.0;
+pub const BOLT_SPEED: f32 = 900.0;
+// chance a destroyed brick lets a power-up fall
+pub const DROP_CHANCE: f64 = 0.15;
+pub const MAX_BALLS: usize = 8;
+// ball speed while the slow power-up lasts
+pub const SLOW: f32 = 0.6;
+// paddle width while the wide power-up lasts
+pub const WIDE: f32 = 1.5;
+
+// Built-in levels, replaced by `*.txt` files in the levels directory when
+// there are any. One line per row of bricks: `.` is empty, `1`-`9` the hits
+// a brick takes and `#` a brick that never breaks.
+const LEVELS: [&str; 4] = [
+    include_str!("../../assets/breakout/level1.txt"),
+    include_str!("../../assets/breakout/level2.txt"),
+    include_str!("../../assets/breakout/level3.txt"),
+    include_str!("../../assets/breakout/level4.txt"),
+];
+
+#[derive(Default)]
+pub enum Scene {
+    #[default]
+    Welcome = 0,
+    Playing,
+    Cleared,
+    GameOver,
+    Victory,
+}
+
+pub struct Brick {
+    pub rect: (f32, f32, f32, f32),
+    // hits left, `None` for an indestructible brick
+    pub hits: Option<i32>,
+}
+
+impl Brick {
+    // true when this hit broke the brick
+    pub fn hit(&mut self) -> bool {
+        match &mut self.hits {
+            Some(hits) => {
+                *hits -= 1;
+                *hits <= 0
+            }
+            None => false,
+        }
+    }
+}
+
+// bricks of a level laid out across the top of a `width` wide field
+pub fn layout(level: &str, width: f32) -> Vec<Brick> {
+    let rows: Vec<&str> = level.lines().filter(|row| !row.trim().is_empty()).collect();
+    let cols = rows
+        .iter()
+        .map(|row| row.chars().count())
+        .max()
+        .unwrap_or(1);
+    let w = (width - 2.0 * PAD as f32) / cols as f32;
+    let h = HEIGHT as f32;
+    let mut bricks = Vec::new();
+    for (row, line) in rows.iter().enumerate() {
+        for (col, cell) in line.chars().enumerate() {
+            let hits = match cell {
+                '#' => None,
+                '1'..='9' => cell.to_digit(10).map(|hits| hits as i32),
+                _ => continue,
+            };
+            bricks.push(Brick {
+                rect: (
+                    PAD as f32 + col as f32 * w + 1.0,
+                    h * (2 + row) as f32 + 1.0,
+                    w - 2.0,
+                    h - 2.0,
+                ),
+                hits,
+            });
+        }
+    }
+    bricks
+}
+
+// level texts from `dir`, in file name order, or the built-in ones
+pub fn levels(dir: &str) -> Vec<String> {
+    let mut files: Vec<std::path::PathBuf> = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "txt"))
+        .collect();
+    files.sort();
+    let custom: Vec<String> = files
+        .iter()
+        .filter_map(|path| std::fs::read_to_string(path).ok())
+        .filter(|level| level.chars().any(|cell| ('1'..='9').contains(&cell)))
+        .collect();
+    match custom.is_empty() {
+        true => LEVELS.iter().map(|level| level.to_string()).collect(),
+        false => custom,
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Power {
+    Wide,
+    Multi,
+    Slow,
+    Laser,
+}
+
+impl Power {
+    pub fn random() -> Self {
+        match rand::rng().random_range(0..4) {
+            0 => Self::Wide,
+            1 => Self::Multi,
+            2 => Self::Slow,
+            _ => Self::Laser,
+        }
+    }
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Wide => "W",
+            Self::Multi => "M",
+            Self::Slow => "S",
+            Self::Laser => "L",
+        }
+    }
+    // seconds the power lasts, multiball is instant
+    pub fn duration(&self) -> f32 {
+        match self {
+            Self::Wide => 12.0,
+            Self::Multi => 0.0,
+            Self::Slow => 8.0,
+            Self::Laser => 10.0,
+        }
+    }
+}
+
+// power-up falling from a broken brick
+pub struct Drop {
+    pub pos: (f32, f32),
+    pub power: Power,
+}
+
+impl Drop {
+    pub const SIZE: (f32, f32) = (HEIGHT as f32 * 1.5, HEIGHT as f32 * 0.75);
+    pub fn update(&mut self, dt: f32) {
+        self.pos.1 += DROP_SPEED * dt;
+    }
+    pub fn caught(&self, paddle: (i32, i32, i32, i32)) -> bool {
+        let (x, y, w, h) = paddle;
+        self.pos.0 + Self::SIZE.0 >= x as f32
+            && self.pos.0 <= (x + w) as f32
+            && self.pos.1 + Self::SIZE.1 >= y as f32
+            && self.pos.1 <= (y + h) as f32
+    }
+}
+
+// laser shot travelling straight up
+pub struct Bolt(pub f32, pub f32);
+
+impl Bolt {
+    pub const LENGTH: f32 = HEIGHT as f32 / 2.0;
+    pub fn update(&mut self, dt: f32) {
+        self.1 -= BOLT_SPEED * dt;
+    }
+    pub fn hits(&self, rect: (f32, f32, f32, f32)) -> bool {
+        let (x, y, w, h) = rect;
+        (x..=x + w).contains(&self.0) && self.1 <= y + h && self.1 + Self::LENGTH >= y
+    }
+}
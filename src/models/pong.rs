@@ -1,75 +1,394 @@
 use crate::*;
+use rand::RngExt;
 
-#[derive(Default, Clone)]
-enum Direction {
+// match lengths to pick from, first to this many points
+pub const TARGETS: [i32; 3] = [11, 21, 5];
+// seconds between a point and the next serve
+pub const SERVE_DELAY: f32 = 1.0;
+// balls a squash game may lose and the pause after each
+pub const LIVES: i32 = 3;
+pub const LOST_PAUSE: f32 = 1.0;
+// ball speeds in pixels per second, each return multiplies by SPEED_UP
+pub const BALL_SPEED: f32 = 600.0;
+pub const SPEED_UP: f32 = 1.05;
+pub const MAX_SPEED: f32 = 1800.0;
+// steepest bounce off a paddle edge, radians from straight back
+pub const MAX_ANGLE: f32 = std::f32::consts::FRAC_PI_3;
+// speed of a key-driven paddle in pixels per second
+pub const PADDLE_SPEED: f32 = 650.0;
+// share of paddle speed handed to the ball when spin is on
+pub const SPIN: f32 = 0.4;
+// longest step integrated at once
+pub const MAX_STEP: f32 = 0.05;
+// seconds between multiball events, and the most balls in play
+pub const MULTIBALL: f32 = 15.0;
+pub const MAX_BALLS: usize = 5;
+// gravity well strength and the radius inside which it stops growing
+pub const WELL_PULL: f32 = 2.0e7;
+pub const WELL_RADIUS: f32 = 80.0;
+
+#[derive(Default)]
+pub enum Scene {
     #[default]
-    Positive = 1,
-    Negative = -1,
+    Welcome = 0,
+    Playing,
+    Summary,
+    GameOver,
 }
 
-#[derive(Default)]
-pub struct Paddle(i32, i32);
+#[derive(Default, Clone, Copy, PartialEq)]
+pub enum Mode {
+    #[default]
+    Squash,
+    Classic,
+    Versus,
+}
+
+impl Mode {
+    pub fn switch(&self) -> Self {
+        match self {
+            Self::Squash => Self::Classic,
+            Self::Classic => Self::Versus,
+            Self::Versus => Self::Squash,
+        }
+    }
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Squash => "Squash",
+            Self::Classic => "Classic",
+            Self::Versus => "Versus",
+        }
+    }
+    // who plays each side
+    pub fn name(&self, side: Side) -> &'static str {
+        match (self, side) {
+            (Self::Versus, Side::Left) => "Left",
+            (Self::Versus, Side::Right) => "Right",
+            (_, Side::Left) => "You",
+            (_, Side::Right) => "CPU",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+pub struct Paddle {
+    pos: (i32, i32),
+    size: (i32, i32),
+    // where the paddle was at the last `track` and how fast it moved since
+    last: (i32, i32),
+    velocity: (f32, f32),
+}
+
+impl Default for Paddle {
+    fn default() -> Self {
+        Self {
+            pos: (0, 0),
+            size: (WIDTH * 2, HEIGHT),
+            last: (0, 0),
+            velocity: (0.0, 0.0),
+        }
+    }
+}
 
 impl Paddle {
-    const SIZE: (i32, i32) = (WIDTH * 2, HEIGHT);
+    // upright paddle for the classic left and right sides
+    pub fn vertical() -> Self {
+        Self {
+            size: (HEIGHT, WIDTH * 2),
+            ..Default::default()
+        }
+    }
     pub fn set_x(&mut self, value: i32) {
-        self.0 = value;
+        self.pos.0 = value;
     }
     pub fn set_y(&mut self, value: i32) {
-        self.1 = value - HEIGHT * 2;
+        self.pos.1 = value - HEIGHT * 2;
     }
     pub fn left(&mut self) {
-        self.0 -= WIDTH;
+        self.pos.0 -= WIDTH;
     }
     pub fn right(&mut self) {
-        self.0 += WIDTH;
+        self.pos.0 += WIDTH;
     }
-    fn place(&self, pos: i32) -> bool {
-        (self.0..=self.0 + Self::SIZE.0).contains(&pos)
+    // moves an upright paddle at PADDLE_SPEED, `dir` -1 up, 1 down, 0 stays
+    pub fn slide(&mut self, dir: i32, field: &Field, dt: f32) {
+        if dir != 0 {
+            let step = (PADDLE_SPEED * dt) as i32 * dir;
+            self.center(self.middle().1 + step, field);
+        }
+    }
+    // moves an upright paddle so its middle is at `y`, kept inside the field
+    pub fn center(&mut self, y: i32, field: &Field) {
+        self.pos.1 = (y - self.size.1 / 2).clamp(field.height.0, field.height.1 - self.size.1);
+    }
+    pub fn middle(&self) -> (i32, i32) {
+        (self.pos.0 + self.size.0 / 2, self.pos.1 + self.size.1 / 2)
+    }
+    // new length along the paddle's long side, keeping its middle in place
+    pub fn resize(&mut self, length: i32) {
+        match self.size.1 > self.size.0 {
+            true => {
+                self.pos.1 += (self.size.1 - length) / 2;
+                self.size.1 = length;
+            }
+            false => {
+                self.pos.0 += (self.size.0 - length) / 2;
+                self.size.0 = length;
+            }
+        }
+    }
+    // once per frame, measures the motion handed on as spin
+    pub fn track(&mut self, dt: f32) {
+        if dt > 0.0 {
+            self.velocity = (
+                (self.pos.0 - self.last.0) as f32 / dt,
+                (self.pos.1 - self.last.1) as f32 / dt,
+            );
+        }
+        self.last = self.pos;
     }
     pub(crate) fn draw(&self) -> (i32, i32, i32, i32) {
-        (self.0, self.1, Self::SIZE.0, Self::SIZE.1)
+        (self.pos.0, self.pos.1, self.size.0, self.size.1)
     }
 }
 
-#[derive(Default)]
+#[derive(Clone)]
 pub struct Ball {
-    pos: (i32, i32),
-    dir: (Direction, Direction),
+    // centre of the ball and its velocity in pixels per second
+    pos: (f32, f32),
+    vel: (f32, f32),
+    speed: f32,
+}
+
+impl Default for Ball {
+    fn default() -> Self {
+        let radius = Self::SIZE as f32 / 2.0;
+        let diagonal = BALL_SPEED * std::f32::consts::FRAC_1_SQRT_2;
+        Self {
+            pos: (radius, radius),
+            vel: (diagonal, diagonal),
+            speed: BALL_SPEED,
+        }
+    }
 }
 
 impl Ball {
     const SIZE: i32 = HEIGHT * 2;
+    pub const RADIUS: f32 = Self::SIZE as f32 / 2.0;
     pub fn draw(&self) -> (i32, i32, i32) {
-        (self.pos.0, self.pos.1, Self::SIZE)
+        (
+            (self.pos.0 - Self::RADIUS) as i32,
+            (self.pos.1 - Self::RADIUS) as i32,
+            Self::SIZE,
+        )
     }
-    pub fn step(&mut self) {
-        self.pos.0 += self.dir.0.clone() as i32;
-        self.pos.1 += self.dir.1.clone() as i32;
+    // moves the ball and returns where it was, for swept collisions
+    pub fn step(&mut self, dt: f32) -> (f32, f32) {
+        let from = self.pos;
+        self.pos.0 += self.vel.0 * dt;
+        self.pos.1 += self.vel.1 * dt;
+        from
     }
-    pub fn check_paddle(&mut self, paddle: &Paddle) -> bool {
-        if paddle.1 == (self.pos.1 + Self::SIZE) && paddle.place(self.pos.0 + Self::SIZE / 2) {
-            self.dir.1 = Direction::Negative;
-            return true;
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+    // slows down or speeds up without changing course
+    pub fn scale(&mut self, factor: f32) {
+        self.speed = (self.speed * factor).min(MAX_SPEED);
+        let length = self.vel.0.hypot(self.vel.1).max(f32::EPSILON);
+        self.vel = (
+            self.vel.0 / length * self.speed,
+            self.vel.1 / length * self.speed,
+        );
+    }
+    // a copy heading off `angle` radians from this ball's course
+    pub fn split(&self, angle: f32) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Self {
+            vel: (
+                self.vel.0 * cos - self.vel.1 * sin,
+                self.vel.0 * sin + self.vel.1 * cos,
+            ),
+            ..self.clone()
         }
-        false
     }
+    // sitting on top of a bottom paddle, waiting to be launched
+    pub fn rest_on(&mut self, paddle: &Paddle) {
+        let (x, y, w, _) = paddle.draw();
+        self.pos = ((x + w / 2) as f32, y as f32 - Self::RADIUS);
+        self.vel = (0.0, 0.0);
+    }
+    // straight up off the paddle, give or take a little
+    pub fn launch(&mut self) {
+        self.speed = BALL_SPEED;
+        let angle = rand::rng().random_range(-MAX_ANGLE / 3.0..MAX_ANGLE / 3.0);
+        self.vel = (angle.sin() * BALL_SPEED, -angle.cos() * BALL_SPEED);
+    }
+    // centred in the field, heading to `side` at a random angle
+    pub fn serve(&mut self, field: &Field, side: Side) {
+        self.pos = (field.width.1 as f32 / 2.0, field.height.1 as f32 / 2.0);
+        self.speed = BALL_SPEED;
+        let angle = rand::rng().random_range(-MAX_ANGLE / 2.0..MAX_ANGLE / 2.0);
+        let sign = match side {
+            Side::Left => -1.0,
+            Side::Right => 1.0,
+        };
+        self.vel = (sign * angle.cos() * BALL_SPEED, angle.sin() * BALL_SPEED);
+    }
+    // Swept test against either kind of paddle: the path travelled since
+    // `from` is checked against the paddle face, so a fast ball cannot skip
+    // over it. The bounce angle follows where the ball met the paddle, the
+    // ball gets faster on every return and, with `spin`, picks up some of
+    // the paddle's own motion.
+    pub fn check_paddle(&mut self, paddle: &Paddle, from: (f32, f32), spin: bool) -> bool {
+        let (x, y, w, h) = paddle.draw();
+        let (x, y, w, h) = (x as f32, y as f32, w as f32, h as f32);
+        let r = Self::RADIUS;
+        let upright = h > w;
+        // face line for the ball centre and which axis crosses it
+        let (face, start, end) = match (upright, self.vel.0 > 0.0) {
+            (false, _) if self.vel.1 > 0.0 => (y - r, from.1, self.pos.1),
+            (true, false) => (x + w + r, from.0, self.pos.0),
+            (true, true) => (x - r, from.0, self.pos.0),
+            _ => return false,
+        };
+        let crossed = match start < end {
+            true => start <= face && face < end,
+            false => end < face && face <= start,
+        };
+        if !crossed {
+            return false;
+        }
+        let t = (face - start) / (end - start);
+        let at = (
+            from.0 + (self.pos.0 - from.0) * t,
+            from.1 + (self.pos.1 - from.1) * t,
+        );
+        let (along, lo, len) = match upright {
+            true => (at.1, y, h),
+            false => (at.0, x, w),
+        };
+        if along < lo - r || along > lo + len + r {
+            return false;
+        }
+        self.pos = at;
+        self.speed = (self.speed * SPEED_UP).min(MAX_SPEED);
+        let offset = ((along - lo - len / 2.0) / (len / 2.0)).clamp(-1.0, 1.0);
+        let mut angle = offset * MAX_ANGLE;
+        if spin {
+            let motion = match upright {
+                true => paddle.velocity.1,
+                false => paddle.velocity.0,
+            };
+            angle = (angle + (motion * SPIN / self.speed).atan()).clamp(-MAX_ANGLE, MAX_ANGLE);
+        }
+        let (sin, cos) = angle.sin_cos();
+        self.vel = match (upright, self.vel.0 > 0.0) {
+            (false, _) => (sin * self.speed, -cos * self.speed),
+            (true, false) => (cos * self.speed, sin * self.speed),
+            (true, true) => (-cos * self.speed, sin * self.speed),
+        };
+        true
+    }
+    // Curves the path towards `well`, pulling harder the closer it gets,
+    // without changing the ball's speed.
+    pub fn pull(&mut self, well: (f32, f32), dt: f32) {
+        let (dx, dy) = (well.0 - self.pos.0, well.1 - self.pos.1);
+        let distance = dx.hypot(dy).max(WELL_RADIUS);
+        let force = WELL_PULL * dt / (distance * distance);
+        self.vel.0 += dx / distance * force;
+        self.vel.1 += dy / distance * force;
+        self.scale(1.0);
+    }
+    // Circle against a rectangle such as a brick or an obstacle: the ball is
+    // reflected on the axis it came in along and left outside the rectangle.
+    pub fn check_rect(&mut self, rect: (f32, f32, f32, f32), from: (f32, f32)) -> bool {
+        let (x, y, w, h) = rect;
+        let closest = (self.pos.0.clamp(x, x + w), self.pos.1.clamp(y, y + h));
+        let (dx, dy) = (self.pos.0 - closest.0, self.pos.1 - closest.1);
+        if dx * dx + dy * dy >= Self::RADIUS * Self::RADIUS {
+            return false;
+        }
+        // the axis it came in along is the one `from` was clear of the rect
+        // grown by the radius, from a corner the shallower overlap decides
+        let r = Self::RADIUS;
+        let clear = (
+            from.0 < x - r || from.0 > x + w + r,
+            from.1 < y - r || from.1 > y + h + r,
+        );
+        let side = match clear {
+            (true, false) => true,
+            (false, true) => false,
+            _ => {
+                let depth_x = (self.pos.0 + r - x).min(x + w + r - self.pos.0);
+                let depth_y = (self.pos.1 + r - y).min(y + h + r - self.pos.1);
+                depth_x < depth_y
+            }
+        };
+        // pushed out along that axis, a moving obstacle may have swept over
+        // `from` already
+        match side {
+            true => match from.0 < x + w / 2.0 {
+                true => (self.pos.0, self.vel.0) = (x - r, -self.vel.0.abs()),
+                false => (self.pos.0, self.vel.0) = (x + w + r, self.vel.0.abs()),
+            },
+            false => match from.1 < y + h / 2.0 {
+                true => (self.pos.1, self.vel.1) = (y - r, -self.vel.1.abs()),
+                false => (self.pos.1, self.vel.1) = (y + h + r, self.vel.1.abs()),
+            },
+        }
+        true
+    }
+    // reflects off the top and bottom, the side the ball left through conceded
+    pub fn check_goal(&mut self, field: &Field) -> Option<Side> {
+        self.bounce_y(field);
+        let r = Self::RADIUS;
+        if self.pos.0 + r <= field.width.0 as f32 {
+            return Some(Side::Left);
+        }
+        if self.pos.0 - r >= field.width.1 as f32 {
+            return Some(Side::Right);
+        }
+        None
+    }
+    // three walls for squash, true when the ball drops out of the bottom
     pub fn check_field(&mut self, field: &Field) -> bool {
-        if self.pos.0 + Self::SIZE == field.width.1 {
-            self.dir.0 = Direction::Negative;
+        let (r, left, right) = (Self::RADIUS, field.width.0 as f32, field.width.1 as f32);
+        if self.pos.0 - r < left {
+            self.pos.0 = 2.0 * (left + r) - self.pos.0;
+            self.vel.0 = self.vel.0.abs();
         }
-        if self.pos.0 == field.width.0 {
-            self.dir.0 = Direction::Positive;
+        if self.pos.0 + r > right {
+            self.pos.0 = 2.0 * (right - r) - self.pos.0;
+            self.vel.0 = -self.vel.0.abs();
         }
-        if self.pos.1 == field.height.0 {
-            self.dir.1 = Direction::Positive;
+        let top = field.height.0 as f32;
+        if self.pos.1 - r < top {
+            self.pos.1 = 2.0 * (top + r) - self.pos.1;
+            self.vel.1 = self.vel.1.abs();
         }
-        if self.pos.1 + Self::SIZE == field.height.1 {
-            self.pos.1 = 0;
+        if self.pos.1 - r >= field.height.1 as f32 {
+            self.pos.1 = top + r;
             return true;
         }
         false
     }
+    fn bounce_y(&mut self, field: &Field) {
+        let (r, top, bottom) = (Self::RADIUS, field.height.0 as f32, field.height.1 as f32);
+        if self.pos.1 - r < top {
+            self.pos.1 = 2.0 * (top + r) - self.pos.1;
+            self.vel.1 = self.vel.1.abs();
+        }
+        if self.pos.1 + r > bottom {
+            self.pos.1 = 2.0 * (bottom - r) - self.pos.1;
+            self.vel.1 = -self.vel.1.abs();
+        }
+    }
 }
 
 #[derive(Default)]
@@ -85,13 +404,252 @@ impl Field {
     }
 }
 
+#[derive(Default)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub fn switch(&self) -> Self {
+        match self {
+            Self::Easy => Self::Normal,
+            Self::Normal => Self::Hard,
+            Self::Hard => Self::Easy,
+        }
+    }
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Easy => "Easy",
+            Self::Normal => "Normal",
+            Self::Hard => "Hard",
+        }
+    }
+    // seconds between looks at the ball
+    fn reaction(&self) -> f32 {
+        match self {
+            Self::Easy => 0.3,
+            Self::Normal => 0.15,
+            Self::Hard => 0.05,
+        }
+    }
+    // paddle speed in pixels per second
+    fn speed(&self) -> f32 {
+        match self {
+            Self::Easy => 300.0,
+            Self::Normal => 450.0,
+            Self::Hard => 700.0,
+        }
+    }
+    // worst prediction miss as a share of the paddle length
+    fn error(&self) -> f32 {
+        match self {
+            Self::Easy => 1.4,
+            Self::Normal => 1.0,
+            Self::Hard => 0.7,
+        }
+    }
+}
+
+// Computer player driving the right-hand paddle.
+#[derive(Default)]
+pub struct Cpu {
+    target: i32,
+    timer: f32,
+}
+
+impl Cpu {
+    pub fn update(
+        &mut self,
+        difficulty: &Difficulty,
+        balls: &[Ball],
+        paddle: &mut Paddle,
+        field: &Field,
+        dt: f32,
+    ) {
+        self.timer -= dt;
+        if self.timer <= 0.0 {
+            self.timer = difficulty.reaction();
+            let miss = (paddle.size.1 as f32 * difficulty.error()) as i32;
+            // chase whichever ball arrives first
+            let aim = balls
+                .iter()
+                .filter_map(|ball| Self::predict(ball, paddle, field))
+                .min_by(|a, b| a.0.total_cmp(&b.0))
+                .map_or(field.height.1 / 2, |(_, y)| y);
+            self.target = aim + rand::rng().random_range(-miss..=miss);
+        }
+        let step = (difficulty.speed() * dt) as i32;
+        let y = paddle.middle().1;
+        paddle.center(y + (self.target - y).clamp(-step, step), field);
+    }
+    // when and where the ball will cross the paddle, folding in wall
+    // bounces, `None` while it travels away
+    fn predict(ball: &Ball, paddle: &Paddle, field: &Field) -> Option<(f32, i32)> {
+        let r = Ball::RADIUS;
+        let (x, w) = (paddle.pos.0 as f32, paddle.size.0 as f32);
+        let distance = match ball.vel.0 > 0.0 {
+            true => x - r - ball.pos.0,
+            false => ball.pos.0 - x - w - r,
+        };
+        if distance < 0.0 || ball.vel.0.abs() < f32::EPSILON {
+            return None;
+        }
+        let time = distance / ball.vel.0.abs();
+        let span = (field.height.1 as f32 - 2.0 * r).max(1.0);
+        let y = (ball.pos.1 - r + ball.vel.1 * time).rem_euclid(2.0 * span);
+        let top = match y > span {
+            true => 2.0 * span - y,
+            false => y,
+        };
+        Some((time, (top + r) as i32))
+    }
+}
+
+// Optional rules picked on the welcome screen.
+#[derive(Default)]
+pub struct Rules {
+    pub multiball: bool,
+    pub obstacles: bool,
+    pub shrink: bool,
+    pub wells: bool,
+}
+
+impl Rules {
+    // paddle length once `score` points have been played
+    pub fn length(&self, score: i32) -> i32 {
+        let length = WIDTH * 2;
+        match self.shrink {
+            true => (length - score * PAD / 2).max(length / 2),
+            false => length,
+        }
+    }
+}
+
+// block sliding up and down through the middle fifths of the field
+pub struct Obstacle {
+    pub rect: (f32, f32, f32, f32),
+    pub speed: f32,
+}
+
+impl Obstacle {
+    pub fn update(&mut self, field: &Field, dt: f32) {
+        self.rect.1 += self.speed * dt;
+        let height = field.height.1 as f32;
+        let (top, bottom) = (height * 0.2, (height * 0.8 - self.rect.3).max(height * 0.2));
+        if self.rect.1 < top || self.rect.1 > bottom {
+            self.rect.1 = self.rect.1.clamp(top, bottom);
+            self.speed = -self.speed;
+        }
+    }
+}
+
+// Obstacles and gravity wells placed for the selected rules.
+#[derive(Default)]
+pub struct Arena {
+    pub obstacles: Vec<Obstacle>,
+    pub wells: Vec<(f32, f32)>,
+}
+
+impl Arena {
+    pub fn new(rules: &Rules, field: &Field) -> Self {
+        let (w, h) = (field.width.1 as f32, field.height.1 as f32);
+        let mut arena = Self::default();
+        if rules.obstacles {
+            let size = (HEIGHT as f32, WIDTH as f32);
+            for (x, speed) in [
+                (w / 2.0 - 3.0 * size.0, 150.0),
+                (w / 2.0 + 2.0 * size.0, -150.0),
+            ] {
+                arena.obstacles.push(Obstacle {
+                    rect: (x, (h - size.1) / 2.0, size.0, size.1),
+                    speed,
+                });
+            }
+        }
+        if rules.wells {
+            arena.wells = vec![(w / 3.0, h / 3.0), (w * 2.0 / 3.0, h * 2.0 / 3.0)];
+        }
+        arena
+    }
+    pub fn update(&mut self, field: &Field, dt: f32) {
+        for obstacle in &mut self.obstacles {
+            obstacle.update(field, dt);
+        }
+    }
+    // bends and bounces a ball that just moved from `from`
+    pub fn apply(&self, ball: &mut Ball, from: (f32, f32), dt: f32) {
+        for well in &self.wells {
+            ball.pull(*well, dt);
+        }
+        for obstacle in &self.obstacles {
+            if ball.check_rect(obstacle.rect, from) {
+                break;
+            }
+        }
+    }
+}
+
+// A two-paddle match: first to `target` points, but once both sides reach
+// deuce the winner needs a two point lead.
+#[derive(Default)]
+pub struct Match {
+    pub points: (i32, i32),
+    pub target: i32,
+    pub hits: (i32, i32),
+    pub rally: i32,
+    pub longest: i32,
+    pub time: f32,
+}
+
+impl Match {
+    pub fn new(target: i32) -> Self {
+        Self {
+            target,
+            ..Default::default()
+        }
+    }
+    pub fn hit(&mut self, side: Side) {
+        match side {
+            Side::Left => self.hits.0 += 1,
+            Side::Right => self.hits.1 += 1,
+        }
+        self.rally += 1;
+        self.longest = self.longest.max(self.rally);
+    }
+    // `side` let the ball through, the other side scores
+    pub fn point(&mut self, side: Side) {
+        match side {
+            Side::Left => self.points.1 += 1,
+            Side::Right => self.points.0 += 1,
+        }
+        self.rally = 0;
+    }
+    pub fn deuce(&self) -> bool {
+        self.points.0.min(self.points.1) >= self.target - 1
+    }
+    pub fn leader(&self) -> Option<Side> {
+        match self.points.0.cmp(&self.points.1) {
+            std::cmp::Ordering::Greater => Some(Side::Left),
+            std::cmp::Ordering::Less => Some(Side::Right),
+            std::cmp::Ordering::Equal => None,
+        }
+    }
+    pub fn winner(&self) -> Option<Side> {
+        let (left, right) = self.points;
+        match left.max(right) >= self.target && (left - right).abs() >= 2 {
+            true => self.leader(),
+            false => None,
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct Score(pub i32, pub i32);
 
 impl Score {
-    pub fn dec(&mut self) {
-        self.0 -= 1;
-    }
     pub fn inc(&mut self) {
         self.0 += 1;
     }
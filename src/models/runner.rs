@@ -1,4 +1,34 @@
 use rand::RngExt;
+
+// recent runs kept on disk and how many the welcome screen lists
+pub const HISTORY: usize = 10;
+pub const RECENT: usize = 3;
+// score between switches of day and night, and seconds a fade takes
+pub const DAY_LENGTH: u32 = 3000;
+pub const FADE: f32 = 2.0;
+// share of the run speed each background layer scrolls at
+pub const CLOUD_PARALLAX: f32 = 0.1;
+pub const MOUNTAIN_PARALLAX: f32 = 0.3;
+// repeat of the ground texture in pixels
+pub const GROUND_TILE: f32 = 32.0;
+// ground passed per wing beat of a pterodactyl
+pub const FLAP: f32 = 60.0;
+// cactus drawings to pick from
+pub const CACTUS_VARIANTS: usize = 3;
+// take-off speed and gravity, pixels per second
+pub const JUMP_SPEED: f32 = 900.0;
+pub const GRAVITY: f32 = 2400.0;
+// gravity scale once the jump key is let go on the way up, and while
+// diving with Down
+pub const JUMP_CUT: f32 = 2.5;
+pub const FAST_FALL: f32 = 3.0;
+// seconds a jump pressed before landing is kept
+pub const JUMP_BUFFER: f32 = 0.12;
+// room between cacti of a cluster, and seconds of slack a jump over a whole
+// pattern part must leave
+pub const CLUSTER_SPACING: f32 = 4.0;
+pub const JUMP_WINDOW: f32 = 0.1;
+
 #[derive(Default)]
 pub enum Scene {
     #[default]
@@ -46,6 +76,14 @@ impl Difficulty {
             Self::Hard => "Hard",
         }
     }
+    pub fn from_label(label: &str) -> Option<Self> {
+        match label {
+            "Easy" => Some(Self::Easy),
+            "Normal" => Some(Self::Normal),
+            "Hard" => Some(Self::Hard),
+            _ => None,
+        }
+    }
     pub fn speed_mul(&self) -> f32 {
         match self {
             Self::Easy => 0.9,
@@ -63,6 +101,47 @@ impl Difficulty {
     pub fn ptero_enabled(&self) -> bool {
         matches!(self, Self::Normal | Self::Hard)
     }
+    // gap after a pattern ending with a pterodactyl or a cactus
+    pub fn pattern_gap(&self, flying: bool) -> f32 {
+        const PTERO_MIN_GAP: f32 = 520.0;
+        const PTERO_MAX_GAP: f32 = 760.0;
+        let (min, max) = match flying {
+            true => (
+                PTERO_MIN_GAP * self.gap_scale(),
+                PTERO_MAX_GAP * self.gap_scale(),
+            ),
+            false => self.limit(),
+        };
+        rand::rng().random_range(min..max)
+    }
+    pub fn cluster_chance(&self) -> f64 {
+        match self {
+            Self::Easy => 0.2,
+            Self::Normal => 0.35,
+            Self::Hard => 0.5,
+        }
+    }
+    pub fn max_cluster(&self) -> usize {
+        match self {
+            Self::Easy => 2,
+            Self::Normal | Self::Hard => 3,
+        }
+    }
+    pub fn mix_chance(&self) -> f64 {
+        match self {
+            Self::Easy => 0.0,
+            Self::Normal => 0.12,
+            Self::Hard => 0.2,
+        }
+    }
+    // seconds every pattern leaves between two moves
+    pub fn reaction(&self) -> f32 {
+        match self {
+            Self::Easy => 0.4,
+            Self::Normal => 0.3,
+            Self::Hard => 0.22,
+        }
+    }
 }
 
 pub enum ObstacleKind {
@@ -70,15 +149,131 @@ pub enum ObstacleKind {
     Pterodactyl(bool), // flies at given y
 }
 
+impl ObstacleKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Cactus(true) => "cactus",
+            Self::Cactus(false) => "big cactus",
+            Self::Pterodactyl(true) => "high pterodactyl",
+            Self::Pterodactyl(false) => "low pterodactyl",
+        }
+    }
+    // hitbox width and height
+    pub fn size(&self) -> (f32, f32) {
+        match self {
+            Self::Cactus(true) => (24.0, 46.0),
+            Self::Cactus(false) => (36.0, 60.0),
+            Self::Pterodactyl(..) => (46.0, 24.0),
+        }
+    }
+}
+
 pub struct Obstacle {
     pub rect: (f32, f32, f32, f32),
     pub kind: ObstacleKind,
+    // drawing of a cactus, wing phase of a pterodactyl
+    pub frame: usize,
+}
+
+impl Obstacle {
+    pub fn frame(&self, distance: f32) -> usize {
+        match self.kind {
+            ObstacleKind::Cactus(..) => self.frame,
+            ObstacleKind::Pterodactyl(..) => self.frame + (distance / FLAP) as usize,
+        }
+    }
+}
+
+// Seconds a full jump keeps the feet above `height`.
+pub fn airtime(height: f32) -> f32 {
+    2.0 * (JUMP_SPEED * JUMP_SPEED - 2.0 * GRAVITY * height).sqrt() / GRAVITY
+}
+
+// Seconds a full jump takes to lift the feet to `height`.
+pub fn rise_time(height: f32) -> f32 {
+    (JUMP_SPEED - (JUMP_SPEED * JUMP_SPEED - 2.0 * GRAVITY * height).sqrt()) / GRAVITY
+}
+
+// Next obstacles as kinds with the free ground after each one, the last
+// carrying the gap to the following pattern. Every spacing lets a full jump
+// at `speed` clear it: clusters shrink until one jump spans them, a
+// pterodactyl after a cactus leaves room to land first and a cactus after a
+// low pterodactyl leaves room to stand up and take off.
+pub fn pattern(difficulty: &Difficulty, speed: f32, flying: bool) -> Vec<(ObstacleKind, f32)> {
+    let mut rng = rand::rng();
+    let dino = Dino::default().w();
+    let reaction = speed * difficulty.reaction();
+    let roll: f64 = rng.random();
+    let mix = match flying {
+        true => difficulty.mix_chance(),
+        false => 0.0,
+    };
+    let mut pieces = if roll < mix {
+        let cactus = ObstacleKind::Cactus(rng.random_bool(0.5));
+        match rng.random_bool(0.5) {
+            true => vec![
+                (cactus, speed * airtime(0.0) + reaction),
+                (ObstacleKind::Pterodactyl(true), 0.0),
+            ],
+            false => {
+                let rise = speed * rise_time(cactus.size().1);
+                vec![
+                    (ObstacleKind::Pterodactyl(false), dino + reaction + rise),
+                    (cactus, 0.0),
+                ]
+            }
+        }
+    } else if flying && roll < mix + 0.35 {
+        vec![(ObstacleKind::Pterodactyl(rng.random_bool(0.5)), 0.0)]
+    } else {
+        let count = match rng.random_bool(difficulty.cluster_chance()) {
+            true => rng.random_range(2..=difficulty.max_cluster()),
+            false => 1,
+        };
+        let mut cluster: Vec<(ObstacleKind, f32)> = (0..count)
+            .map(|_| (ObstacleKind::Cactus(rng.random_bool(0.4)), CLUSTER_SPACING))
+            .collect();
+        while cluster.len() > 1 {
+            let width: f32 = cluster.iter().map(|(kind, _)| kind.size().0).sum::<f32>()
+                + CLUSTER_SPACING * (cluster.len() - 1) as f32;
+            let height = cluster
+                .iter()
+                .map(|(kind, _)| kind.size().1)
+                .fold(0.0, f32::max);
+            if speed * (airtime(height) - JUMP_WINDOW) >= width + dino {
+                break;
+            }
+            cluster.pop();
+        }
+        cluster
+    };
+    if let Some((kind, gap)) = pieces.last_mut() {
+        let flying = matches!(kind, ObstacleKind::Pterodactyl(..));
+        *gap = difficulty
+            .pattern_gap(flying)
+            .max(speed * airtime(0.0) + reaction);
+    }
+    pieces
+}
+
+// What the dino is doing, with the leg frame while on the ground.
+pub enum Pose {
+    Run(usize),
+    Jump,
+    Duck(usize),
+    Dead,
 }
 
 #[derive(Default)]
 pub struct Dino {
     pub ducking: bool,
     pub jumping: bool,
+    pub dead: bool,
+    // jump key down, and diving after Down in the air
+    pub holding: bool,
+    pub falling: bool,
+    // seconds left on a buffered jump
+    pub buffer: f32,
     pub leg_state: bool,
     pub leg_timer: f32,
     pub y: f32,
@@ -95,4 +290,145 @@ impl Dino {
     pub fn height(&self) -> f32 {
         if self.ducking { 30.0 } else { 48.0 }
     }
+    // jump key down, the jump happens on the next update that allows it
+    pub fn press(&mut self) {
+        if !self.holding {
+            self.holding = true;
+            self.buffer = JUMP_BUFFER;
+        }
+    }
+    pub fn release(&mut self) {
+        self.holding = false;
+    }
+    // Down: duck, diving when in the air
+    pub fn duck(&mut self) {
+        self.ducking = true;
+        if self.jumping && !self.falling {
+            self.falling = true;
+            self.vy = self.vy.max(0.0);
+        }
+    }
+    pub fn stand(&mut self) {
+        self.ducking = false;
+    }
+    // moves the dino over a ground line at `ground`
+    pub fn update(&mut self, dt: f32, ground: f32) {
+        if self.buffer > 0.0 && !self.jumping {
+            self.buffer = 0.0;
+            self.jumping = true;
+            self.vy = -JUMP_SPEED;
+        }
+        self.buffer -= dt;
+        let top = ground - self.height();
+        if self.jumping {
+            let scale = match (self.falling, self.vy < 0.0 && !self.holding) {
+                (true, _) => FAST_FALL,
+                (false, true) => JUMP_CUT,
+                (false, false) => 1.0,
+            };
+            self.vy += GRAVITY * scale * dt;
+            self.y += self.vy * dt;
+            if self.y >= top {
+                self.jumping = false;
+                self.falling = false;
+            }
+        } else {
+            self.y = top;
+            self.vy = 0.0;
+            self.leg_timer += dt;
+            if self.leg_timer >= 0.12 {
+                self.leg_timer = 0.0;
+                self.leg_state = !self.leg_state;
+            }
+        }
+    }
+    pub fn pose(&self) -> Pose {
+        let leg = self.leg_state as usize;
+        match (self.dead, self.ducking, self.jumping) {
+            (true, ..) => Pose::Dead,
+            (_, true, _) => Pose::Duck(leg),
+            (_, _, true) => Pose::Jump,
+            _ => Pose::Run(leg),
+        }
+    }
+}
+
+// A finished run as kept in the history.
+pub struct Run {
+    pub difficulty: String,
+    pub score: u32,
+    pub distance: u32,
+    pub cause: String,
+}
+
+impl Run {
+    pub fn to_line(&self) -> String {
+        format!(
+            "{} {} {} {}",
+            self.difficulty, self.score, self.distance, self.cause
+        )
+    }
+    pub fn from_line(line: &str) -> Option<Self> {
+        let mut parts = line.splitn(4, ' ');
+        let difficulty = Difficulty::from_label(parts.next()?)?;
+        Some(Self {
+            difficulty: difficulty.label().to_string(),
+            score: parts.next()?.parse().ok()?,
+            distance: parts.next()?.parse().ok()?,
+            cause: parts.next()?.to_string(),
+        })
+    }
+}
+
+// Background layers scrolling slower than the ground: clouds as (x, y) and
+// mountains as (x, half width, height) standing on the horizon.
+#[derive(Default)]
+pub struct Scenery {
+    pub clouds: Vec<(f32, f32)>,
+    pub mountains: Vec<(f32, f32, f32)>,
+}
+
+impl Scenery {
+    pub const CLOUD: (f32, f32) = (70.0, 20.0);
+    pub fn new(width: f32, sky: f32) -> Self {
+        let mut rng = rand::rng();
+        Self {
+            clouds: (0..4)
+                .map(|i| {
+                    (
+                        width * i as f32 / 4.0 + rng.random_range(0.0..width / 8.0),
+                        rng.random_range(sky * 0.2..sky * 0.6),
+                    )
+                })
+                .collect(),
+            mountains: (0..5)
+                .map(|i| Self::mountain(width * i as f32 / 4.0))
+                .collect(),
+        }
+    }
+    fn mountain(x: f32) -> (f32, f32, f32) {
+        let mut rng = rand::rng();
+        (
+            x,
+            rng.random_range(90.0..180.0),
+            rng.random_range(40.0..110.0),
+        )
+    }
+    // moves the layers by `dx` of ground, recycling what left on the left
+    pub fn update(&mut self, dx: f32, width: f32, sky: f32) {
+        let mut rng = rand::rng();
+        for cloud in &mut self.clouds {
+            cloud.0 -= dx * CLOUD_PARALLAX;
+            if cloud.0 + Self::CLOUD.0 < 0.0 {
+                cloud.0 = width + rng.random_range(0.0..width / 4.0);
+                cloud.1 = rng.random_range(sky * 0.2..sky * 0.6);
+            }
+        }
+        for mountain in &mut self.mountains {
+            mountain.0 -= dx * MOUNTAIN_PARALLAX;
+            if mountain.0 + mountain.1 < 0.0 {
+                *mountain = Self::mountain(width + rng.random_range(0.0..width / 4.0));
+            }
+        }
+    }
 }
@@ -1,5 +1,92 @@
-use crate::*;
 use rand::RngExt;
+use std::{cmp::Reverse, collections::BinaryHeap, collections::VecDeque};
+
+const TURNS: usize = 3;
+const DIRECTIONS: [(i32, i32); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+pub const ROUNDS: u32 = 3;
+pub const ITEM_LIFE: f32 = 8.0;
+pub const ITEM_SPAWN: f32 = 10.0;
+pub const APPLE_LIFE: f32 = 6.0;
+pub const STARVE: f32 = 12.0;
+
+#[derive(Default)]
+pub enum Scene {
+    #[default]
+    Welcome,
+    Playing,
+    RoundOver,
+    GameOver,
+}
+
+#[derive(Default, PartialEq, Clone, Copy)]
+pub enum Mode {
+    #[default]
+    Endless,
+    TimeAttack(u32), // seconds on the clock
+    Survival,
+    Zen,
+    Autopilot,
+    Versus,
+}
+
+impl Mode {
+    pub fn switch(&self) -> Self {
+        match self {
+            Self::Endless => Self::TimeAttack(60),
+            Self::TimeAttack(60) => Self::TimeAttack(120),
+            Self::TimeAttack(_) => Self::Survival,
+            Self::Survival => Self::Zen,
+            Self::Zen => Self::Autopilot,
+            Self::Autopilot => Self::Versus,
+            Self::Versus => Self::Endless,
+        }
+    }
+    pub fn label(&self) -> String {
+        match self {
+            Self::Endless => String::from("Endless"),
+            Self::TimeAttack(limit) => format!("Time Attack {limit}s"),
+            Self::Survival => String::from("Survival"),
+            Self::Zen => String::from("Zen"),
+            Self::Autopilot => String::from("Autopilot"),
+            Self::Versus => String::from("Versus"),
+        }
+    }
+}
+
+#[derive(Default)]
+pub enum Board {
+    Small,
+    #[default]
+    Medium,
+    Large,
+}
+
+impl Board {
+    pub fn switch(&self) -> Self {
+        match self {
+            Self::Small => Self::Medium,
+            Self::Medium => Self::Large,
+            Self::Large => Self::Small,
+        }
+    }
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Small => "Small",
+            Self::Medium => "Medium",
+            Self::Large => "Large",
+        }
+    }
+    // grid of square cells that fits `size`, with this many cells along the shorter side
+    pub fn field(&self, size: (i32, i32)) -> (i32, i32) {
+        let cells = match self {
+            Self::Small => 18,
+            Self::Medium => 27,
+            Self::Large => 36,
+        };
+        let cell = (size.0.min(size.1) / cells).max(1);
+        ((size.0 / cell).max(1), (size.1 / cell).max(1))
+    }
+}
 
 #[derive(Default)]
 pub struct Score(i32, u64);
@@ -8,6 +95,12 @@ impl Score {
     pub fn inc(&mut self) {
         self.0 += 1;
     }
+    pub fn add(&mut self, points: i32) {
+        self.0 += points;
+    }
+    pub fn get(&self) -> i32 {
+        self.0
+    }
     pub fn level(&self) -> u64 {
         self.1
     }
@@ -24,12 +117,50 @@ impl Score {
 #[derive(Default)]
 pub struct Apple(pub i32, pub i32);
 
-impl Apple {
-    pub fn new(field: (i32, i32)) -> Self {
-        Self(
-            rand::rng().random_range(0..field.0),
-            rand::rng().random_range(0..field.1),
-        )
+pub enum Power {
+    Golden,
+    Slow,
+    Shrink,
+    Ghost,
+}
+
+impl Power {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Golden => "GOLD",
+            Self::Slow => "SLOW",
+            Self::Shrink => "SHRINK",
+            Self::Ghost => "GHOST",
+        }
+    }
+    // seconds the effect lasts once picked up, zero for instant ones
+    pub fn duration(&self) -> f32 {
+        match self {
+            Self::Slow => 6.0,
+            Self::Ghost => 5.0,
+            Self::Golden | Self::Shrink => 0.0,
+        }
+    }
+}
+
+pub struct Item {
+    pub cell: (i32, i32),
+    pub power: Power,
+    pub life: f32,
+}
+
+impl Item {
+    pub fn new(cell: (i32, i32)) -> Self {
+        Self {
+            cell,
+            power: match rand::rng().random_range(0..10) {
+                0..4 => Power::Golden,
+                4..6 => Power::Slow,
+                6..8 => Power::Shrink,
+                _ => Power::Ghost,
+            },
+            life: ITEM_LIFE,
+        }
     }
 }
 
@@ -37,42 +168,227 @@ impl Apple {
 pub struct Snake {
     pub body: Vec<(i32, i32)>,
     pub direction: (i32, i32),
+    pub ghost: f32,
+    pub phantom: bool,
+    turns: VecDeque<(i32, i32)>,
 }
 
 impl Snake {
     pub fn set(&mut self, field: (i32, i32)) {
-        *self = Self {
-            body: vec![(
+        self.spawn(
+            (
                 rand::rng().random_range(0..field.0),
                 rand::rng().random_range(0..field.1),
-            )],
-            direction: (1, 0),
+            ),
+            (1, 0),
+        );
+    }
+    pub fn spawn(&mut self, head: (i32, i32), direction: (i32, i32)) {
+        *self = Self {
+            body: vec![head],
+            direction,
+            ghost: 0.0,
+            phantom: false,
+            turns: VecDeque::new(),
         }
     }
-    pub fn update(&mut self) -> Option<(i32, i32)> {
+    pub fn head(&self) -> (i32, i32) {
+        self.body[0]
+    }
+    pub fn update(&mut self, field: (i32, i32)) -> Option<(i32, i32)> {
+        // one buffered turn per tick, checked against the last direction moved
+        while let Some((x, y)) = self.turns.pop_front() {
+            if self.direction != (x, y) && self.direction != (-x, -y) {
+                self.direction = (x, y);
+                break;
+            }
+        }
         let (mut x, mut y) = self.body[0];
-        x = check_limit(x + self.direction.0, COLS);
-        y = check_limit(y + self.direction.1, ROWS);
-        if self.body.contains(&(x, y)) {
+        x = check_limit(x + self.direction.0, field.0);
+        y = check_limit(y + self.direction.1, field.1);
+        if self.ghost <= 0.0 && !self.phantom && self.body.contains(&(x, y)) {
             None
         } else {
             self.body.insert(0, (x, y));
             Some((x, y))
         }
     }
+    pub fn shrink(&mut self, segments: usize) {
+        self.body
+            .truncate(self.body.len().saturating_sub(segments).max(1));
+    }
     pub fn direction(&mut self, x: i32, y: i32) {
-        if self.direction != (x, y) && self.direction != (-x, -y) {
-            self.direction = (x, y);
+        let last = self.turns.back().copied().unwrap_or(self.direction);
+        if self.turns.len() < TURNS && last != (x, y) && last != (-x, -y) {
+            self.turns.push_back((x, y));
         }
     }
 }
 
+// wraps a coordinate onto `0..limit`, `limit` itself is off the board and
+// comes back in at 0
 fn check_limit(coord: i32, limit: i32) -> i32 {
     if coord < 0 {
         limit - 1
-    } else if coord > limit {
+    } else if coord >= limit {
         0
     } else {
         coord
     }
 }
+
+#[derive(Default)]
+pub struct Autopilot {
+    field: (i32, i32),
+    length: usize,
+    hunger: i32,
+}
+
+impl Autopilot {
+    pub fn new(field: (i32, i32)) -> Self {
+        Self {
+            field,
+            length: 0,
+            hunger: 0,
+        }
+    }
+    // Follows the Hamiltonian cycle, which on its own can never collide, and
+    // only cuts across it towards the apple into free cells ahead of the head
+    // and behind the tail in cycle order, leaving room for the body to pass.
+    pub fn steer(&mut self, snake: &Snake, apple: &Apple) -> (i32, i32) {
+        (self.length, self.hunger) = match self.length == snake.body.len() {
+            true => (self.length, self.hunger + 1),
+            false => (snake.body.len(), 0),
+        };
+        let cells = self.field.0 * self.field.1;
+        let (head, apple) = (snake.body[0], (apple.0, apple.1));
+        let next = self.cycle(head);
+        // circling without ever reaching the apple: the plain cycle passes it
+        if self.hunger > cells {
+            return self.heading(head, next);
+        }
+        let ahead = |to: (i32, i32)| (self.order(to) - self.order(head)).rem_euclid(cells);
+        let len = snake.body.len() as i32;
+        let room = match len {
+            1 => cells,
+            _ => ahead(snake.body[snake.body.len() - 1]),
+        };
+        let goal = ahead(apple);
+        // free cells left between head and tail must outlast the whole body
+        let shortcut = |cell: &(i32, i32)| {
+            let skip = ahead(*cell);
+            !snake.body.contains(cell)
+                && self.heading(head, *cell) != back(snake.direction)
+                && room - skip - 1 > len
+                && (goal >= room || skip <= goal)
+        };
+        if let Some(path) = self.path(&snake.body, apple, snake.direction)
+            && let Some(step) = path.first().filter(|step| shortcut(step))
+        {
+            return self.heading(head, *step);
+        }
+        DIRECTIONS
+            .into_iter()
+            .map(|dir| self.step(head, dir))
+            .filter(shortcut)
+            .max_by_key(|&cell| ahead(cell))
+            .map_or(self.heading(head, next), |cell| self.heading(head, cell))
+    }
+    fn index(&self, (x, y): (i32, i32)) -> usize {
+        (y * self.field.0 + x) as usize
+    }
+    fn step(&self, (x, y): (i32, i32), (dx, dy): (i32, i32)) -> (i32, i32) {
+        (
+            check_limit(x + dx, self.field.0),
+            check_limit(y + dy, self.field.1),
+        )
+    }
+    fn heading(&self, from: (i32, i32), to: (i32, i32)) -> (i32, i32) {
+        DIRECTIONS
+            .into_iter()
+            .find(|&dir| self.step(from, dir) == to)
+            .unwrap_or((1, 0))
+    }
+    fn distance(&self, a: (i32, i32), b: (i32, i32)) -> i32 {
+        let (dx, dy) = ((a.0 - b.0).abs(), (a.1 - b.1).abs());
+        dx.min(self.field.0 - dx) + dy.min(self.field.1 - dy)
+    }
+    // A* over the wrapping grid. A body segment `i` of a snake with `len`
+    // segments is still in the way on move `t` while `t <= len - i`.
+    fn path(
+        &self,
+        body: &[(i32, i32)],
+        goal: (i32, i32),
+        direction: (i32, i32),
+    ) -> Option<Vec<(i32, i32)>> {
+        let cells = (self.field.0 * self.field.1) as usize;
+        let mut segment = vec![None; cells];
+        for (i, &cell) in body.iter().enumerate() {
+            segment[self.index(cell)] = Some(i);
+        }
+        let mut cost = vec![i32::MAX; cells];
+        let mut from = vec![None; cells];
+        let mut open = BinaryHeap::new();
+        cost[self.index(body[0])] = 0;
+        open.push(Reverse((self.distance(body[0], goal), 0, body[0])));
+        while let Some(Reverse((_, g, cell))) = open.pop() {
+            if cell == goal {
+                let mut path = vec![cell];
+                while let Some(prev) = from[self.index(path[path.len() - 1])] {
+                    path.push(prev);
+                }
+                path.pop();
+                path.reverse();
+                return Some(path);
+            }
+            if g > cost[self.index(cell)] {
+                continue;
+            }
+            for dir in DIRECTIONS {
+                if g == 0 && dir == back(direction) {
+                    continue;
+                }
+                let next = self.step(cell, dir);
+                let idx = self.index(next);
+                let blocked = segment[idx].is_some_and(|i| g < (body.len() - i) as i32);
+                if blocked || g + 1 >= cost[idx] {
+                    continue;
+                }
+                cost[idx] = g + 1;
+                from[idx] = Some(cell);
+                open.push(Reverse((g + 1 + self.distance(next, goal), g + 1, next)));
+            }
+        }
+        None
+    }
+    // Serpentine Hamiltonian cycle, closed through the wrapping edge. It runs
+    // along columns when the width is even, along rows otherwise, so the field
+    // needs one even side.
+    fn cycle(&self, (x, y): (i32, i32)) -> (i32, i32) {
+        let (w, h) = self.field;
+        match w % 2 == 0 {
+            true => match (x % 2 == 0, y) {
+                (true, y) if y < h - 1 => (x, y + 1),
+                (false, y) if y > 0 => (x, y - 1),
+                _ => self.step((x, y), (1, 0)),
+            },
+            false => match (y % 2 == 0, x) {
+                (true, x) if x < w - 1 => (x + 1, y),
+                (false, x) if x > 0 => (x - 1, y),
+                _ => self.step((x, y), (0, 1)),
+            },
+        }
+    }
+    // position of a cell along `cycle`
+    fn order(&self, (x, y): (i32, i32)) -> i32 {
+        let (w, h) = self.field;
+        match w % 2 == 0 {
+            true => x * h + if x % 2 == 0 { y } else { h - 1 - y },
+            false => y * w + if y % 2 == 0 { x } else { w - 1 - x },
+        }
+    }
+}
+
+fn back((x, y): (i32, i32)) -> (i32, i32) {
+    (-x, -y)
+}